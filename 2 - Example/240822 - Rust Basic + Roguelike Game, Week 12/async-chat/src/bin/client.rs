@@ -1,48 +1,240 @@
+use async_chat::backoff::Backoff;
+use async_chat::codec::{JsonCodec, WireFormat};
+use async_chat::command::parse_command;
+use async_chat::rt::sync::mpsc;
+use async_chat::rt::{self, net, Race};
 use async_chat::utils::{self, ChatResult};
 use async_chat::FromClient;
 use async_chat::FromServer;
-use async_std::io;
-use async_std::net;
-use async_std::prelude::*;
-use async_std::task;
-use std::sync::Arc;
+use async_chat::ModAction;
+use futures::io;
+use futures::prelude::*;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
 
-async fn send_commands(mut to_server: net::TcpStream) -> ChatResult<()> {
-    println!(
-        "Commands:\n\
-        join GROUP\n\
-        post GROUP MESSAGE...\n\
-        Type Control-D (on Unix) or Control-Z (Windows) to close the connection."
-    );
+/// The requests that got us into our current state on the server,
+/// which we repeat after reconnecting.
+#[derive(Default)]
+struct Replay {
+    nickname: Option<Arc<String>>,
+    groups: Vec<Arc<String>>,
+}
 
-    let mut command_lines = io::BufReader::new(io::stdin()).lines();
+impl Replay {
+    fn record(&mut self, request: &FromClient) {
+        match request {
+            FromClient::SetNickname { nickname } => self.nickname = Some(nickname.clone()),
+            FromClient::Join { group_name } if !self.groups.contains(group_name) => {
+                self.groups.push(group_name.clone());
+            }
+            _ => {}
+        }
+    }
 
-    while let Some(command_result) = command_lines.next().await {
+    fn forget(&mut self, group_name: &Arc<String>) {
+        self.groups.retain(|group| group != group_name);
+    }
+
+    fn requests(&self) -> Vec<FromClient> {
+        let nickname = self
+            .nickname
+            .iter()
+            .map(|nickname| FromClient::SetNickname {
+                nickname: nickname.clone(),
+            });
+        let joins = self.groups.iter().map(|group_name| FromClient::Join {
+            group_name: group_name.clone(),
+        });
+
+        nickname.chain(joins).collect()
+    }
+}
+
+/// Read commands from the standard input for as long as it stays open.
+/// This outlives any one connection, so commands go through a channel.
+/// It blocks, so it gets a thread of its own rather than a task.
+fn read_commands(commands: mpsc::UnboundedSender<FromClient>) -> ChatResult<()> {
+    for command_result in std::io::stdin().lock().lines() {
         let command = command_result?;
         let request = match parse_command(&command) {
             Some(request) => request,
-            None => continue,
+            None => {
+                eprintln!("Unrecognized command: {command:?}");
+                continue;
+            }
         };
 
-        utils::send_as_json(&mut to_server, &request).await?;
-        to_server.flush().await?;
+        commands.send(request)?;
+    }
+
+    Ok(())
+}
+
+/// The sending side of a connection. Both our commands and the requests
+/// we make on our own, like fetching missed messages, go through here,
+/// so the lock keeps their packets from getting mixed up.
+struct ToServer {
+    socket: rt::sync::Mutex<net::TcpStream>,
+    wire_format: WireFormat,
+}
+
+impl ToServer {
+    async fn send(&self, request: &FromClient) -> ChatResult<()> {
+        let mut socket = self.socket.lock().await;
+
+        utils::send_packet(&mut *socket, &self.wire_format, request).await?;
+        socket.flush().await?;
+
+        Ok(())
+    }
+}
+
+async fn send_commands(
+    to_server: &ToServer,
+    commands: &mut mpsc::UnboundedReceiver<FromClient>,
+    replay: &Mutex<Replay>,
+) -> ChatResult<()> {
+    let requests = replay.lock().unwrap().requests();
+
+    for request in requests {
+        to_server.send(&request).await?;
+    }
+
+    while let Some(request) = commands.recv().await {
+        replay.lock().unwrap().record(&request);
+        to_server.send(&request).await?;
     }
 
     Ok(())
 }
 
-async fn handle_replies(from_server: net::TcpStream) -> ChatResult<()> {
-    let buffered = io::BufReader::new(from_server);
-    let mut reply_stream = utils::receive_as_json(buffered);
+/// Keeps track of the last message seen in each group, to notice
+/// when some went missing.
+#[derive(Default)]
+struct Sequences(HashMap<Arc<String>, u64>);
+
+#[derive(Debug, PartialEq)]
+enum Seen {
+    /// The message we were expecting, or the first one in its group.
+    Next,
+    /// A message that skipped ahead. The messages numbered `from` through
+    /// `to` never arrived.
+    Gap { from: u64, to: u64 },
+    /// A message older than the last one, which we must have asked for.
+    Old,
+}
 
-    while let Some(reply) = reply_stream.next().await {
-        match reply? {
+impl Sequences {
+    fn observe(&mut self, group_name: &Arc<String>, sequence: u64) -> Seen {
+        let last = match self.0.get_mut(group_name) {
+            Some(last) => last,
+            None => {
+                self.0.insert(group_name.clone(), sequence);
+                return Seen::Next;
+            }
+        };
+
+        if sequence <= *last {
+            return Seen::Old;
+        }
+
+        let expected = *last + 1;
+        *last = sequence;
+
+        if sequence == expected {
+            Seen::Next
+        } else {
+            Seen::Gap {
+                from: expected,
+                to: sequence - 1,
+            }
+        }
+    }
+}
+
+async fn handle_replies(
+    mut from_server: io::BufReader<net::TcpStream>,
+    to_server: &ToServer,
+    replay: &Mutex<Replay>,
+) -> ChatResult<()> {
+    let wire_format = to_server.wire_format;
+    let mut sequences = Sequences::default();
+
+    while let Some(reply) = utils::receive_packet(&mut from_server, &wire_format).await? {
+        match reply {
             FromServer::Message {
                 group_name,
+                sender,
+                message,
+                sequence,
+                timestamp,
+            } => {
+                let seen = sequences.observe(&group_name, sequence);
+                let time = utils::format_time(timestamp);
+                let late = if seen == Seen::Old { " (late)" } else { "" };
+
+                println!("[{time} #{sequence}] message posted to {group_name} by {sender}{late}: {message}");
+
+                if let Seen::Gap { from, to } = seen {
+                    let fetch = FromClient::Fetch {
+                        group_name,
+                        from,
+                        to,
+                    };
+
+                    to_server.send(&fetch).await?;
+                }
+            }
+            FromServer::MemberJoined {
+                group_name,
+                nickname,
+            } => {
+                println!("* {nickname} joined {group_name}");
+            }
+            FromServer::MemberLeft {
+                group_name,
+                nickname,
+            } => {
+                println!("* {nickname} left {group_name}");
+            }
+            FromServer::Typing {
+                group_name,
+                nickname,
+            } => {
+                println!("* {nickname} is typing in {group_name}...");
+            }
+            FromServer::Moderated {
+                group_name,
+                action,
+                by,
+            } => {
+                let what = match action {
+                    ModAction::Kick => "kicked you from",
+                    ModAction::Ban => "banned you from",
+                    ModAction::Unban => "unbanned you in",
+                    ModAction::Mute => "muted you in",
+                    ModAction::Unmute => "unmuted you in",
+                    ModAction::TransferOwnership => "made you the owner of",
+                };
+
+                if let ModAction::Kick | ModAction::Ban = action {
+                    replay.lock().unwrap().forget(&group_name);
+                }
+
+                println!("{by} {what} {group_name}");
+            }
+            FromServer::Notice {
+                group_name,
+                from,
                 message,
             } => {
-                println!("message posted to {group_name}: {message}");
+                println!("({from} in {group_name}) {message}");
             }
+            FromServer::ShuttingDown => {
+                println!("the server is shutting down");
+            }
+            FromServer::Welcome { .. } => {}
             FromServer::Error(message) => {
                 println!("error from server: {message}");
             }
@@ -52,63 +244,133 @@ async fn handle_replies(from_server: net::TcpStream) -> ChatResult<()> {
     Ok(())
 }
 
-fn main() -> ChatResult<()> {
-    let address = std::env::args().nth(1).expect("Usage: client ADDRESS:PORT");
+/// Ask the server to switch to `wire_format`.
+///
+/// We do this even for JSON: it tells the server that we understand
+/// every kind of packet, not just the ones from before the handshake.
+async fn negotiate(
+    mut to_server: &net::TcpStream,
+    from_server: &mut io::BufReader<net::TcpStream>,
+    wire_format: WireFormat,
+) -> ChatResult<()> {
+    utils::send_packet(
+        &mut to_server,
+        &JsonCodec,
+        &FromClient::Hello { wire_format },
+    )
+    .await?;
+    to_server.flush().await?;
 
-    task::block_on(async {
-        let socket = net::TcpStream::connect(address).await?;
-        socket.set_nodelay(true)?;
+    match utils::receive_packet(from_server, &JsonCodec).await? {
+        Some(FromServer::Welcome {
+            wire_format: accepted,
+        }) if accepted == wire_format => Ok(()),
+        Some(FromServer::Error(message)) => Err(message.into()),
+        _ => Err(format!("Server did not accept the {wire_format:?} wire format").into()),
+    }
+}
 
-        let to_server = send_commands(socket.clone());
-        let from_server = handle_replies(socket);
+/// Why a connection ended.
+enum Ended {
+    /// The standard input was closed, so we're done.
+    ByUser,
+    /// The server closed the connection, so we should try again.
+    ByServer,
+}
 
-        from_server.race(to_server).await?;
+async fn run_connection(
+    address: &str,
+    wire_format: WireFormat,
+    commands: &mut mpsc::UnboundedReceiver<FromClient>,
+    replay: &Mutex<Replay>,
+    backoff: &mut Backoff,
+) -> ChatResult<Ended> {
+    let socket = net::TcpStream::connect(address).await?;
+    socket.set_nodelay(true)?;
 
-        Ok(())
-    })
+    let mut buffered = io::BufReader::new(socket.clone());
+    negotiate(&socket, &mut buffered, wire_format).await?;
+    backoff.reset();
+
+    let to_server = ToServer {
+        socket: rt::sync::Mutex::new(socket),
+        wire_format,
+    };
+
+    let sending = async {
+        send_commands(&to_server, commands, replay).await?;
+        Ok(Ended::ByUser)
+    };
+    let receiving = async {
+        handle_replies(buffered, &to_server, replay).await?;
+        Ok(Ended::ByServer)
+    };
+
+    receiving.race(sending).await
 }
 
-/// Parse a line (presumably read from the standard input) as a 'Request'.
-fn parse_command(line: &str) -> Option<FromClient> {
-    let (command, rest) = get_next_token(line)?;
+fn main() -> ChatResult<()> {
+    let usage = "Usage: client ADDRESS:PORT [json|binary]";
+    let address = std::env::args().nth(1).expect(usage);
+    let wire_format = match std::env::args().nth(2).as_deref() {
+        None | Some("json") => WireFormat::Json,
+        Some("binary") => WireFormat::Binary,
+        Some(_) => panic!("{usage}"),
+    };
 
-    if command == "post" {
-        let (group, rest) = get_next_token(rest)?;
-        let message = rest.trim_start().to_string();
+    println!(
+        "Commands:\n\
+        nick NICKNAME\n\
+        join GROUP\n\
+        post GROUP MESSAGE...\n\
+        typing GROUP\n\
+        kick|ban|unban|mute|unmute|owner GROUP NICKNAME\n\
+        Type Control-D (on Unix) or Control-Z (Windows) to close the connection."
+    );
 
-        return Some(FromClient::Post {
-            group_name: Arc::new(group.to_string()),
-            message: Arc::new(message),
-        });
-    } else if command == "join" {
-        let (group, rest) = get_next_token(rest)?;
+    let (sender, mut commands) = mpsc::unbounded_channel();
 
-        if !rest.trim_start().is_empty() {
-            return None;
+    std::thread::spawn(move || {
+        if let Err(error) = read_commands(sender) {
+            eprintln!("error reading commands: {error}");
         }
+    });
 
-        return Some(FromClient::Join {
-            group_name: Arc::new(group.to_string()),
-        });
-    } else {
-        eprintln!("Unrecognized command: {:?}", line);
-        return None;
-    }
-}
+    rt::block_on(async {
+        let replay = Mutex::new(Replay::default());
+        let mut backoff = Backoff::default();
 
-/// Given a string 'input', return 'Some((token, rest))',
-/// where 'token' is the first run of non-whitespace characters in 'input',
-/// and 'rest' is the rest of the string.
-/// If the string contains no non-whitespace characters, return 'None'.
-fn get_next_token(mut input: &str) -> Option<(&str, &str)> {
-    input = input.trim_start();
+        loop {
+            let connection =
+                run_connection(&address, wire_format, &mut commands, &replay, &mut backoff);
 
-    if input.is_empty() {
-        return None;
-    }
+            match connection.await {
+                Ok(Ended::ByUser) => return Ok(()),
+                Ok(Ended::ByServer) => eprintln!("connection closed by server"),
+                Err(error) => eprintln!("connection failed: {error}"),
+            }
 
-    match input.find(char::is_whitespace) {
-        Some(space) => Some((&input[0..space], &input[space..])),
-        None => Some((input, "")),
-    }
+            let delay = backoff.next_delay();
+            eprintln!("reconnecting in {delay:?}...");
+            rt::sleep(delay).await;
+
+            if commands.is_closed() && commands.is_empty() {
+                return Ok(());
+            }
+        }
+    })
+}
+
+#[test]
+fn test_sequences_detect_gaps() {
+    let mut sequences = Sequences::default();
+    let dogs = Arc::new("Dogs".to_string());
+    let cats = Arc::new("Cats".to_string());
+
+    assert_eq!(sequences.observe(&dogs, 7), Seen::Next);
+    assert_eq!(sequences.observe(&dogs, 8), Seen::Next);
+    assert_eq!(sequences.observe(&cats, 1), Seen::Next);
+    assert_eq!(sequences.observe(&dogs, 12), Seen::Gap { from: 9, to: 11 });
+    assert_eq!(sequences.observe(&dogs, 10), Seen::Old);
+    assert_eq!(sequences.observe(&dogs, 13), Seen::Next);
 }
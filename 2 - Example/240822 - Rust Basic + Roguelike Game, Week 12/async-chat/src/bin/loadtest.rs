@@ -0,0 +1,444 @@
+//! Put a server under load: connect many clients, spread them over a few
+//! groups, have each post at a steady rate, and report how long messages
+//! took to arrive and how many never did.
+//!
+//! The server's flood protection applies as usual, so to find out what the
+//! server itself can take, start it with limits to match, for example
+//! `--post-rate 1000 --post-burst 1000 --group-rate 100000 --group-burst 100000`.
+
+use async_chat::codec::{JsonCodec, WireFormat};
+use async_chat::rt::{self, net};
+use async_chat::utils::{self, ChatResult};
+use async_chat::{FromClient, FromServer};
+use futures::io::BufReader;
+use futures::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: loadtest ADDRESS [OPTIONS]
+
+Options:
+    --clients N    how many clients to connect (default 100)
+    --groups N     how many groups to spread them over (default 10)
+    --rate N       posts per second from each client (default 1)
+    --duration N   seconds to keep posting (default 10)
+    --drain N      seconds to wait for the last messages to arrive (default 2)
+    --size N       bytes in each message (default 64)
+    --wire FORMAT  json or binary (default binary)";
+
+/// Every simulated client's nickname starts with this.
+const NICKNAME_PREFIX: &str = "load-";
+
+struct Settings {
+    address: String,
+    clients: usize,
+    groups: usize,
+    rate: f64,
+    duration: Duration,
+    drain: Duration,
+    size: usize,
+    wire_format: WireFormat,
+}
+
+impl Settings {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Settings, String> {
+        let mut settings = Settings {
+            address: args.next().ok_or("Missing ADDRESS")?,
+            clients: 100,
+            groups: 10,
+            rate: 1.0,
+            duration: Duration::from_secs(10),
+            drain: Duration::from_secs(2),
+            size: 64,
+            wire_format: WireFormat::Binary,
+        };
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {flag}"))?;
+
+            match flag.as_str() {
+                "--clients" => settings.clients = parse(&flag, &value)?,
+                "--groups" => settings.groups = parse(&flag, &value)?,
+                "--rate" => settings.rate = parse(&flag, &value)?,
+                "--duration" => settings.duration = seconds(&flag, &value)?,
+                "--drain" => settings.drain = seconds(&flag, &value)?,
+                "--size" => settings.size = parse(&flag, &value)?,
+                "--wire" => {
+                    settings.wire_format = match value.as_str() {
+                        "json" => WireFormat::Json,
+                        "binary" => WireFormat::Binary,
+                        _ => return Err(format!("Unknown wire format {value:?}")),
+                    }
+                }
+                _ => return Err(format!("Unknown option {flag}")),
+            }
+        }
+
+        let rate = settings.rate;
+        if settings.clients == 0 || settings.groups == 0 || !(rate.is_finite() && rate > 0.0) {
+            return Err("--clients, --groups and --rate must be more than zero".to_string());
+        }
+
+        // Posts are spaced `interval` apart, which must come out as a
+        // duration that isn't zero, or the posting never stops.
+        match settings.interval() {
+            Some(interval) if !interval.is_zero() => {}
+            _ => return Err(format!("--rate {rate:e} is out of range")),
+        }
+
+        Ok(settings)
+    }
+
+    /// How long each client waits between posts.
+    fn interval(&self) -> Option<Duration> {
+        Duration::try_from_secs_f64(1.0 / self.rate).ok()
+    }
+
+    fn group_of(&self, client: usize) -> usize {
+        client % self.groups
+    }
+
+    /// How many clients are in `group`.
+    fn members(&self, group: usize) -> u64 {
+        (0..self.clients)
+            .filter(|&client| self.group_of(client) == group)
+            .count() as u64
+    }
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {flag}: {value:?}"))
+}
+
+/// Parse a number of seconds, which may have a fraction.
+fn seconds(flag: &str, value: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(parse(flag, value)?)
+        .map_err(|_| format!("{flag} must be a number of seconds, not {value:?}"))
+}
+
+/// What one client saw.
+#[derive(Default)]
+struct Stats {
+    posted: u64,
+    /// Posts the server refused, usually for going over a rate limit.
+    rejected: u64,
+    received: u64,
+    /// Messages the sequence numbers say were skipped, because the
+    /// client's subscription fell behind.
+    skipped: u64,
+    latencies: Vec<Duration>,
+}
+
+impl Stats {
+    fn merge(&mut self, other: Stats) {
+        self.posted += other.posted;
+        self.rejected += other.rejected;
+        self.received += other.received;
+        self.skipped += other.skipped;
+        self.latencies.extend(other.latencies);
+    }
+}
+
+/// A client that has connected and joined its group, ready to go.
+struct Client {
+    to_server: net::TcpStream,
+    from_server: BufReader<net::TcpStream>,
+    wire_format: WireFormat,
+    group_name: Arc<String>,
+}
+
+impl Client {
+    async fn connect(settings: &Settings, index: usize) -> ChatResult<Client> {
+        let mut to_server = net::TcpStream::connect(&settings.address).await?;
+        to_server.set_nodelay(true)?;
+        let mut from_server = BufReader::new(to_server.clone());
+
+        let wire_format = settings.wire_format;
+        let hello = FromClient::Hello { wire_format };
+        utils::send_packet(&mut to_server, &JsonCodec, &hello).await?;
+
+        match utils::receive_packet(&mut from_server, &JsonCodec).await? {
+            Some(FromServer::Welcome { .. }) => {}
+            _ => return Err("Server did not accept the wire format".into()),
+        }
+
+        let mut client = Client {
+            to_server,
+            from_server,
+            wire_format,
+            group_name: Arc::new(format!("load-{}", settings.group_of(index))),
+        };
+
+        let nickname = Arc::new(format!("{NICKNAME_PREFIX}{index}"));
+        client
+            .send(FromClient::SetNickname {
+                nickname: nickname.clone(),
+            })
+            .await?;
+        client
+            .send(FromClient::Join {
+                group_name: client.group_name.clone(),
+            })
+            .await?;
+
+        // Wait until we are in, so that every client is in before anyone posts.
+        loop {
+            match client.receive().await? {
+                FromServer::MemberJoined {
+                    nickname: joined, ..
+                } if joined == nickname => return Ok(client),
+                FromServer::Error(message) => return Err(message.into()),
+                _ => {}
+            }
+        }
+    }
+
+    async fn send(&mut self, request: FromClient) -> ChatResult<()> {
+        utils::send_packet(&mut self.to_server, &self.wire_format, &request).await?;
+        self.to_server.flush().await?;
+
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> ChatResult<FromServer> {
+        match utils::receive_packet(&mut self.from_server, &self.wire_format).await? {
+            Some(packet) => Ok(packet),
+            None => Err("The server closed the connection".into()),
+        }
+    }
+
+    /// Post at `settings.rate` from `start` for `settings.duration`, while
+    /// recording what arrives until the drain period is over too. Each
+    /// message starts with when it was sent, in microseconds since `origin`.
+    async fn run(
+        self,
+        settings: Arc<Settings>,
+        origin: Instant,
+        start: Instant,
+    ) -> ChatResult<Stats> {
+        let Client {
+            mut to_server,
+            mut from_server,
+            wire_format,
+            group_name,
+        } = self;
+
+        let stop_posting = start + settings.duration;
+        let stop_receiving = stop_posting + settings.drain;
+        let interval = settings.interval().unwrap();
+
+        let posting = async {
+            let mut posted = 0;
+
+            loop {
+                // Keep to the schedule, rather than drifting by however
+                // long each post took.
+                let due = start + interval * posted;
+                if due >= stop_posting {
+                    return Ok::<_, utils::ChatError>(posted as u64);
+                }
+                rt::sleep(due.saturating_duration_since(Instant::now())).await;
+
+                let sent = origin.elapsed().as_micros();
+                let mut message = format!("{sent}:");
+                let padding = settings.size.saturating_sub(message.len());
+                message.extend(std::iter::repeat_n('x', padding));
+
+                let post = FromClient::Post {
+                    group_name: group_name.clone(),
+                    message: Arc::new(message),
+                };
+                utils::send_packet(&mut to_server, &wire_format, &post).await?;
+                to_server.flush().await?;
+                posted += 1;
+            }
+        };
+
+        let receiving = async {
+            let mut stats = Stats::default();
+            let mut last_sequence = None;
+
+            loop {
+                let left = stop_receiving.saturating_duration_since(Instant::now());
+                let received = utils::receive_packet(&mut from_server, &wire_format);
+
+                let packet = match rt::timeout(left, received).await {
+                    Ok(Ok(Some(packet))) => packet,
+                    Ok(Ok(None)) => return Err("The server closed the connection".into()),
+                    Ok(Err(error)) => return Err(error),
+                    Err(_) => return Ok(stats),
+                };
+
+                match packet {
+                    FromServer::Message {
+                        sender,
+                        message,
+                        sequence,
+                        ..
+                    } => {
+                        if let Some(last) = last_sequence {
+                            stats.skipped += sequence.saturating_sub(last + 1);
+                        }
+                        last_sequence = Some(sequence);
+
+                        if let Some(latency) = latency(&sender, &message, origin) {
+                            stats.received += 1;
+                            stats.latencies.push(latency);
+                        }
+                    }
+                    FromServer::Error(_) => stats.rejected += 1,
+                    _ => {}
+                }
+            }
+        };
+
+        let (posted, mut stats) = futures::future::try_join(posting, receiving).await?;
+        stats.posted = posted;
+
+        Ok(stats)
+    }
+}
+
+/// If `message` was posted by one of our clients, return how long ago.
+fn latency(sender: &str, message: &str, origin: Instant) -> Option<Duration> {
+    if !sender.starts_with(NICKNAME_PREFIX) {
+        return None;
+    }
+
+    let (sent, _padding) = message.split_once(':')?;
+    let sent = Duration::from_micros(sent.parse().ok()?);
+
+    Some(origin.elapsed().saturating_sub(sent))
+}
+
+/// Return the latency that `percent` percent of messages arrived within.
+/// `sorted` must be in ascending order and not empty.
+fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    // Nearest rank, allowing for `percent` not being exact in binary.
+    let rank = (percent * sorted.len() as f64 / 100.0 - 1e-9).ceil() as usize;
+
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn report(settings: &Settings, per_client: Vec<Stats>) {
+    let mut expected = 0;
+    let mut total = Stats::default();
+
+    // Every post the server took should have reached everyone in its group,
+    // the sender included.
+    for (index, stats) in per_client.into_iter().enumerate() {
+        let members = settings.members(settings.group_of(index));
+        expected += stats.posted.saturating_sub(stats.rejected) * members;
+        total.merge(stats);
+    }
+
+    let dropped = expected.saturating_sub(total.received);
+    let seconds = settings.duration.as_secs_f64();
+
+    println!(
+        "Posts:       {} sent, {} rejected by the server",
+        total.posted, total.rejected
+    );
+    println!(
+        "Deliveries:  {} expected, {} received, {dropped} dropped ({:.2}%), {} skipped by lagging subscriptions",
+        expected,
+        total.received,
+        dropped as f64 * 100.0 / expected.max(1) as f64,
+        total.skipped
+    );
+    println!(
+        "Throughput:  {:.1} posts/s, {:.1} deliveries/s",
+        total.posted as f64 / seconds,
+        total.received as f64 / seconds
+    );
+
+    if total.latencies.is_empty() {
+        println!("Latency:     nothing arrived");
+        return;
+    }
+
+    total.latencies.sort();
+    let latencies = &total.latencies;
+    println!(
+        "Latency:     p50 {:.2?}, p90 {:.2?}, p99 {:.2?}, p99.9 {:.2?}, max {:.2?}",
+        percentile(latencies, 50.0),
+        percentile(latencies, 90.0),
+        percentile(latencies, 99.0),
+        percentile(latencies, 99.9),
+        latencies[latencies.len() - 1]
+    );
+}
+
+fn main() -> ChatResult<()> {
+    let settings = match Settings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => Arc::new(settings),
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "{} clients in {} groups, each posting {}/s for {:?}, {:?} with {}-byte messages",
+        settings.clients,
+        settings.groups,
+        settings.rate,
+        settings.duration,
+        settings.wire_format,
+        settings.size
+    );
+
+    rt::block_on(async {
+        let origin = Instant::now();
+        let connecting = (0..settings.clients).map(|index| Client::connect(&settings, index));
+        let clients = futures::future::try_join_all(connecting).await?;
+        println!("Connected in {:.2?}", origin.elapsed());
+
+        let start = Instant::now();
+        let running: Vec<_> = clients
+            .into_iter()
+            .map(|client| rt::spawn(client.run(settings.clone(), origin, start)))
+            .collect();
+
+        let mut per_client = Vec::new();
+        for stats in futures::future::join_all(running).await {
+            per_client.push(stats?);
+        }
+
+        report(&settings, per_client);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn test_percentiles() {
+    let latencies: Vec<_> = (1..=1000).map(Duration::from_millis).collect();
+
+    assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(500));
+    assert_eq!(percentile(&latencies, 99.9), Duration::from_millis(999));
+    assert_eq!(percentile(&latencies, 100.0), Duration::from_millis(1000));
+    assert_eq!(percentile(&latencies[..1], 50.0), Duration::from_millis(1));
+}
+
+#[test]
+fn test_clients_are_spread_over_the_groups() {
+    let settings = Settings::from_args(
+        ["127.0.0.1:0", "--clients", "7", "--groups", "3"]
+            .into_iter()
+            .map(String::from),
+    )
+    .unwrap();
+
+    assert_eq!(
+        (0..3)
+            .map(|group| settings.members(group))
+            .collect::<Vec<_>>(),
+        [3, 2, 2]
+    );
+}
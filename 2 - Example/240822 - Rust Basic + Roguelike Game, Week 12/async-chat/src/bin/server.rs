@@ -0,0 +1,26 @@
+use async_chat::rt;
+use async_chat::server::config::{self, Config};
+use async_chat::server::plugin::Plugins;
+use async_chat::server::Server;
+use async_chat::utils::ChatResult;
+
+fn main() -> ChatResult<()> {
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{message}\n\n{}", config::USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    rt::block_on(async {
+        let server = Server::bind(config, Plugins::builtin()).await?;
+
+        ctrlc::set_handler({
+            let shutdown = server.shutdown();
+            move || shutdown.request()
+        })?;
+
+        server.run().await
+    })
+}
@@ -0,0 +1,396 @@
+use async_chat::command::parse_command;
+use async_chat::utils;
+use async_chat::{FromClient, FromServer, ModAction};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::sync::Arc;
+
+/// The name of the pane for anything that doesn't belong to a group.
+const SERVER_PANE: &str = "*server*";
+
+/// How many lines PageUp and PageDown scroll by.
+const PAGE: usize = 10;
+
+/// Everything the client shows, and what the user is typing.
+pub struct App {
+    /// The server pane, followed by one pane per group, in the order joined.
+    pub panes: Vec<Pane>,
+    pub selected: usize,
+    pub input: Input,
+    pub quit: bool,
+}
+
+pub struct Pane {
+    pub name: Arc<String>,
+    pub lines: Vec<Line>,
+    /// Messages that arrived while another pane was selected.
+    pub unread: usize,
+    /// How many lines up from the bottom the view is scrolled.
+    pub scroll: usize,
+}
+
+pub struct Line {
+    pub kind: Kind,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Message,
+    /// Joins, leaves and other news about the group.
+    Event,
+    Error,
+}
+
+impl App {
+    pub fn new() -> App {
+        let mut app = App {
+            panes: vec![Pane::new(Arc::new(SERVER_PANE.to_string()))],
+            selected: 0,
+            input: Input::default(),
+            quit: false,
+        };
+
+        app.push(
+            0,
+            Kind::Event,
+            "Type /join GROUP to join a group. Tab switches between groups, Esc quits.".to_string(),
+        );
+
+        app
+    }
+
+    /// Handle a key press. Return a request to send, if the key completed one.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<FromClient> {
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if control => self.quit = true,
+            KeyCode::Enter => return self.submit(),
+            KeyCode::Tab => self.select((self.selected + 1) % self.panes.len()),
+            KeyCode::BackTab => {
+                self.select((self.selected + self.panes.len() - 1) % self.panes.len());
+            }
+            KeyCode::PageUp => {
+                let pane = &mut self.panes[self.selected];
+                pane.scroll = (pane.scroll + PAGE).min(pane.lines.len().saturating_sub(1));
+            }
+            KeyCode::PageDown => {
+                let pane = &mut self.panes[self.selected];
+                pane.scroll = pane.scroll.saturating_sub(PAGE);
+            }
+            KeyCode::Up => self.input.older(),
+            KeyCode::Down => self.input.newer(),
+            KeyCode::Left => self.input.left(),
+            KeyCode::Right => self.input.right(),
+            KeyCode::Home => self.input.cursor = 0,
+            KeyCode::End => self.input.cursor = self.input.text.chars().count(),
+            KeyCode::Backspace => self.input.backspace(),
+            KeyCode::Char(c) => self.input.insert(c),
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Turn the input line into a request. A line starting with `/` is
+    /// a command if `parse_command` knows it, like `/join Dogs`; anything
+    /// else, including commands for server plugins like `/roll`,
+    /// is posted to the selected group.
+    fn submit(&mut self) -> Option<FromClient> {
+        let line = self.input.take()?;
+
+        if line == "/quit" {
+            self.quit = true;
+            return None;
+        }
+
+        if let Some(request) = line.strip_prefix('/').and_then(parse_command) {
+            if let FromClient::Join { group_name } = &request {
+                let index = self.pane(group_name);
+                self.select(index);
+            }
+
+            return Some(request);
+        }
+
+        if self.selected == 0 {
+            self.push(
+                0,
+                Kind::Error,
+                format!("Not a command: {line:?}. Join a group to post to it."),
+            );
+            return None;
+        }
+
+        Some(FromClient::Post {
+            group_name: self.panes[self.selected].name.clone(),
+            message: Arc::new(line),
+        })
+    }
+
+    pub fn handle_packet(&mut self, packet: FromServer) {
+        match packet {
+            FromServer::Message {
+                group_name,
+                sender,
+                message,
+                timestamp,
+                ..
+            } => {
+                let index = self.pane(&group_name);
+                let time = utils::format_time(timestamp);
+                self.push(index, Kind::Message, format!("{time} <{sender}> {message}"));
+            }
+            FromServer::MemberJoined {
+                group_name,
+                nickname,
+            } => {
+                let index = self.pane(&group_name);
+                self.push(index, Kind::Event, format!("* {nickname} joined"));
+            }
+            FromServer::MemberLeft {
+                group_name,
+                nickname,
+            } => {
+                let index = self.pane(&group_name);
+                self.push(index, Kind::Event, format!("* {nickname} left"));
+            }
+            FromServer::Moderated {
+                group_name,
+                action,
+                by,
+            } => {
+                let what = match action {
+                    ModAction::Kick => "kicked you",
+                    ModAction::Ban => "banned you",
+                    ModAction::Unban => "unbanned you",
+                    ModAction::Mute => "muted you",
+                    ModAction::Unmute => "unmuted you",
+                    ModAction::TransferOwnership => "made you the owner",
+                };
+
+                let index = self.pane(&group_name);
+                self.push(index, Kind::Error, format!("* {by} {what}"));
+            }
+            FromServer::Notice {
+                group_name,
+                from,
+                message,
+            } => {
+                let index = self.pane(&group_name);
+                self.push(index, Kind::Event, format!("({from}) {message}"));
+            }
+            FromServer::Error(message) => {
+                // Errors don't say which group they are about, so show
+                // them where the user is looking.
+                self.push(self.selected, Kind::Error, message);
+            }
+            FromServer::ShuttingDown => {
+                self.push(0, Kind::Error, "The server is shutting down".to_string());
+            }
+            FromServer::Typing { .. } | FromServer::Welcome { .. } => {}
+        }
+    }
+
+    pub fn disconnected(&mut self, reason: String) {
+        self.push(0, Kind::Error, reason);
+        self.select(0);
+    }
+
+    /// Return the index of the pane for `group_name`, adding one if needed.
+    fn pane(&mut self, group_name: &Arc<String>) -> usize {
+        let found = self.panes.iter().position(|pane| pane.name == *group_name);
+
+        found.unwrap_or_else(|| {
+            self.panes.push(Pane::new(group_name.clone()));
+            self.panes.len() - 1
+        })
+    }
+
+    fn push(&mut self, index: usize, kind: Kind, text: String) {
+        let pane = &mut self.panes[index];
+        pane.lines.push(Line { kind, text });
+
+        // Keep a scrolled-back view where it is.
+        if pane.scroll > 0 {
+            pane.scroll += 1;
+        }
+
+        if index != self.selected && kind == Kind::Message {
+            pane.unread += 1;
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        self.panes[index].unread = 0;
+    }
+}
+
+impl Pane {
+    fn new(name: Arc<String>) -> Pane {
+        Pane {
+            name,
+            lines: Vec::new(),
+            unread: 0,
+            scroll: 0,
+        }
+    }
+}
+
+/// The input line, with the lines entered before it.
+#[derive(Default)]
+pub struct Input {
+    pub text: String,
+    /// Where the cursor is, in characters.
+    pub cursor: usize,
+    history: Vec<String>,
+    /// Which entry of `history` is being shown, if the user is browsing it.
+    browsing: Option<usize>,
+}
+
+impl Input {
+    /// Take the line that was entered, if it isn't blank, and remember it.
+    fn take(&mut self) -> Option<String> {
+        let line = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.browsing = None;
+
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        self.history.push(line.clone());
+
+        Some(line)
+    }
+
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.byte_offset(), c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.byte_offset());
+        }
+    }
+
+    fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    fn older(&mut self) {
+        let index = match self.browsing {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.show(Some(index));
+    }
+
+    fn newer(&mut self) {
+        match self.browsing {
+            Some(index) if index + 1 < self.history.len() => self.show(Some(index + 1)),
+            Some(_) => self.show(None),
+            None => {}
+        }
+    }
+
+    fn show(&mut self, browsing: Option<usize>) {
+        self.browsing = browsing;
+        self.text = browsing.map_or_else(String::new, |index| self.history[index].clone());
+        self.cursor = self.text.chars().count();
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(offset, _)| offset)
+    }
+}
+
+#[cfg(test)]
+fn type_line(app: &mut App, line: &str) -> Option<FromClient> {
+    for c in line.chars() {
+        app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+    }
+
+    app.handle_key(KeyEvent::from(KeyCode::Enter))
+}
+
+#[cfg(test)]
+fn message(group_name: &str, text: &str) -> FromServer {
+    FromServer::Message {
+        group_name: Arc::new(group_name.to_string()),
+        sender: Arc::new("jimb".to_string()),
+        message: Arc::new(text.to_string()),
+        sequence: 1,
+        timestamp: 0,
+    }
+}
+
+#[test]
+fn test_unread_counts_and_posting_to_the_selected_group() {
+    let mut app = App::new();
+
+    assert!(matches!(
+        type_line(&mut app, "/join Dogs"),
+        Some(FromClient::Join { group_name }) if *group_name == "Dogs"
+    ));
+    type_line(&mut app, "/join Cats");
+    assert_eq!(app.panes[app.selected].name.as_str(), "Cats");
+
+    // Plain lines, and commands the client doesn't know, go to the group.
+    assert!(matches!(
+        type_line(&mut app, "/roll 2d6"),
+        Some(FromClient::Post { group_name, message })
+            if *group_name == "Cats" && *message == "/roll 2d6"
+    ));
+
+    app.handle_packet(message("Dogs", "Woof"));
+    app.handle_packet(message("Dogs", "Bark"));
+    app.handle_packet(message("Cats", "Meow"));
+    assert_eq!(app.panes[1].unread, 2);
+    assert_eq!(app.panes[2].unread, 0);
+
+    app.handle_key(KeyEvent::from(KeyCode::BackTab));
+    assert_eq!(app.panes[app.selected].name.as_str(), "Dogs");
+    assert_eq!(app.panes[1].unread, 0);
+}
+
+#[test]
+fn test_input_history() {
+    let mut app = App::new();
+    type_line(&mut app, "/join Dogs");
+    type_line(&mut app, "first");
+    type_line(&mut app, "second");
+
+    app.handle_key(KeyEvent::from(KeyCode::Up));
+    assert_eq!(app.input.text, "second");
+    app.handle_key(KeyEvent::from(KeyCode::Up));
+    assert_eq!(app.input.text, "first");
+    app.handle_key(KeyEvent::from(KeyCode::Down));
+    assert_eq!(app.input.text, "second");
+    app.handle_key(KeyEvent::from(KeyCode::Down));
+    assert_eq!(app.input.text, "");
+
+    // Editing happens at the cursor.
+    type_line(&mut app, "ac");
+    app.handle_key(KeyEvent::from(KeyCode::Up));
+    app.handle_key(KeyEvent::from(KeyCode::Left));
+    app.handle_key(KeyEvent::from(KeyCode::Char('b')));
+    assert_eq!(app.input.text, "abc");
+}
@@ -0,0 +1,101 @@
+//! A full-screen client: one scrolling pane per group, a list of groups
+//! with unread counts, and an input line that keeps its history.
+
+use async_chat::codec::WireFormat;
+use async_chat::rt::{self, net};
+use async_chat::utils::{self, ChatResult};
+use async_chat::{FromClient, FromServer};
+use crossterm::event::EventStream;
+use futures::prelude::*;
+use futures::{io, stream};
+use ratatui::DefaultTerminal;
+
+mod app;
+mod ui;
+
+use app::App;
+
+enum Event {
+    Terminal(std::io::Result<crossterm::event::Event>),
+    Server(ChatResult<FromServer>),
+    Disconnected,
+}
+
+fn main() -> ChatResult<()> {
+    let address = match std::env::args().nth(1) {
+        Some(address) => address,
+        None => {
+            eprintln!("Usage: tui ADDRESS:PORT");
+            std::process::exit(1);
+        }
+    };
+
+    rt::block_on(async {
+        let socket = net::TcpStream::connect(address).await?;
+
+        // Set up the terminal only once we know there is a server to talk to,
+        // and put it back however the session ends.
+        let mut terminal = ratatui::init();
+        let result = run(&mut terminal, socket).await;
+        ratatui::restore();
+
+        result
+    })
+}
+
+async fn run(terminal: &mut DefaultTerminal, socket: net::TcpStream) -> ChatResult<()> {
+    let mut to_server = socket.clone();
+
+    // Ask for the full protocol; the rest of the conversation stays JSON.
+    let hello = FromClient::Hello {
+        wire_format: WireFormat::Json,
+    };
+    utils::send_as_json(&mut to_server, &hello).await?;
+
+    let from_server = utils::receive_as_json(io::BufReader::new(socket))
+        .map(Event::Server)
+        .chain(stream::iter([Event::Disconnected]));
+    let from_terminal = EventStream::new().map(Event::Terminal);
+    let mut events = Box::pin(stream::select(from_terminal, from_server));
+
+    let mut app = App::new();
+    let mut connected = true;
+
+    while !app.quit {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+
+        let event = match events.next().await {
+            Some(event) => event,
+            None => break,
+        };
+
+        match event {
+            Event::Terminal(event) => {
+                let key = match event? {
+                    crossterm::event::Event::Key(key) => key,
+                    _ => continue,
+                };
+
+                if let Some(request) = app.handle_key(key) {
+                    if connected {
+                        utils::send_as_json(&mut to_server, &request).await?;
+                        to_server.flush().await?;
+                    }
+                }
+            }
+            Event::Server(Ok(packet)) => app.handle_packet(packet),
+            Event::Server(Err(error)) => {
+                app.disconnected(format!("Connection failed: {error}"));
+                connected = false;
+            }
+            Event::Disconnected => {
+                if connected {
+                    app.disconnected("The server closed the connection. Esc quits.".to_string());
+                    connected = false;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::app::{App, Kind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+const SIDEBAR_WIDTH: u16 = 24;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [sidebar, main] =
+        Layout::horizontal([Constraint::Length(SIDEBAR_WIDTH), Constraint::Min(20)])
+            .areas(frame.area());
+    let [messages, input] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(main);
+
+    draw_groups(frame, app, sidebar);
+    draw_messages(frame, app, messages);
+    draw_input(frame, app, input);
+}
+
+fn draw_groups(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .panes
+        .iter()
+        .map(|pane| {
+            let text = match pane.unread {
+                0 => pane.name.to_string(),
+                unread => format!("{} ({unread})", pane.name),
+            };
+
+            let style = if pane.unread > 0 {
+                Style::new().add_modifier(Modifier::BOLD)
+            } else {
+                Style::new()
+            };
+
+            ListItem::new(text).style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::bordered().title("Groups"))
+        .highlight_style(Style::new().fg(Color::Black).bg(Color::Cyan));
+    let mut state = ListState::default().with_selected(Some(app.selected));
+
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_messages(frame: &mut Frame, app: &App, area: Rect) {
+    let pane = &app.panes[app.selected];
+    let block = Block::bordered().title(pane.name.as_str());
+    let height = block.inner(area).height as usize;
+
+    // Show the lines that fit, ending `scroll` lines from the bottom.
+    let end = pane.lines.len() - pane.scroll.min(pane.lines.len());
+    let start = end.saturating_sub(height);
+
+    let lines: Vec<Line> = pane.lines[start..end]
+        .iter()
+        .map(|line| {
+            let style = match line.kind {
+                Kind::Message => Style::new(),
+                Kind::Event => Style::new().fg(Color::DarkGray),
+                Kind::Error => Style::new().fg(Color::Red),
+            };
+
+            Line::styled(line.text.as_str(), style)
+        })
+        .collect();
+
+    let title = if pane.scroll > 0 {
+        block.title_bottom(format!("scrolled back {} lines", pane.scroll))
+    } else {
+        block
+    };
+
+    frame.render_widget(Paragraph::new(lines).block(title), area);
+}
+
+fn draw_input(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::bordered().title("Message, or /command");
+    let inner = block.inner(area);
+
+    // Keep the cursor in view when the line is wider than the box.
+    let width = inner.width.saturating_sub(1) as usize;
+    let skip = app.input.cursor.saturating_sub(width);
+    let visible: String = app.input.text.chars().skip(skip).collect();
+
+    frame.render_widget(Paragraph::new(visible).block(block), area);
+    frame.set_cursor_position((inner.x + (app.input.cursor - skip) as u16, inner.y));
+}
@@ -0,0 +1,156 @@
+use crate::utils::ChatResult;
+use futures::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::marker::Unpin;
+
+/// The largest binary frame we are willing to allocate a buffer for.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// A way of turning packets into bytes on the wire and back again.
+///
+/// A codec is responsible for both serialization and framing: `encode`
+/// returns a complete frame, and `read_frame` reads exactly one frame
+/// back from the stream, which `decode` then turns into a packet.
+pub trait Codec {
+    /// Serialize `packet` as one complete frame, ready to be written.
+    fn encode<P: Serialize>(&self, packet: &P) -> ChatResult<Vec<u8>>;
+
+    /// Deserialize a packet from a frame returned by `read_frame`.
+    fn decode<P: DeserializeOwned>(&self, frame: &[u8]) -> ChatResult<P>;
+
+    /// Read the next frame from `inbound`.
+    /// Return `None` if the stream ended cleanly between two frames.
+    fn read_frame<S>(
+        &self,
+        inbound: &mut S,
+    ) -> impl Future<Output = ChatResult<Option<Vec<u8>>>> + Send
+    where
+        S: AsyncBufRead + Unpin + Send;
+}
+
+/// One JSON document per line. This is the original protocol,
+/// and what every connection speaks until it negotiates something else.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn encode<P: Serialize>(&self, packet: &P) -> ChatResult<Vec<u8>> {
+        let mut json = serde_json::to_vec(packet)?;
+        json.push(b'\n');
+
+        Ok(json)
+    }
+
+    fn decode<P: DeserializeOwned>(&self, frame: &[u8]) -> ChatResult<P> {
+        Ok(serde_json::from_slice(frame)?)
+    }
+
+    async fn read_frame<S>(&self, inbound: &mut S) -> ChatResult<Option<Vec<u8>>>
+    where
+        S: AsyncBufRead + Unpin + Send,
+    {
+        let mut line = Vec::new();
+
+        if inbound.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        // Strip the line ending the same way `BufRead::lines` does.
+        if line.last() == Some(&b'\n') {
+            line.pop();
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+}
+
+/// `bincode`-encoded packets, each preceded by its length
+/// as a big-endian `u32`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BinaryCodec;
+
+impl Codec for BinaryCodec {
+    fn encode<P: Serialize>(&self, packet: &P) -> ChatResult<Vec<u8>> {
+        let body = bincode::serialize(packet)?;
+
+        if body.len() > MAX_FRAME_LEN {
+            return Err(format!("Frame of {} bytes is too large", body.len()).into());
+        }
+
+        let mut frame = Vec::with_capacity(4 + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+
+        Ok(frame)
+    }
+
+    fn decode<P: DeserializeOwned>(&self, frame: &[u8]) -> ChatResult<P> {
+        Ok(bincode::deserialize(frame)?)
+    }
+
+    async fn read_frame<S>(&self, inbound: &mut S) -> ChatResult<Option<Vec<u8>>>
+    where
+        S: AsyncBufRead + Unpin + Send,
+    {
+        let mut prefix = [0; 4];
+
+        // A stream that ends before the first byte of the prefix ended cleanly,
+        // but one that ends anywhere after that was cut off mid-frame.
+        if inbound.read(&mut prefix[..1]).await? == 0 {
+            return Ok(None);
+        }
+
+        inbound.read_exact(&mut prefix[1..]).await?;
+
+        let len = u32::from_be_bytes(prefix) as usize;
+
+        if len > MAX_FRAME_LEN {
+            return Err(format!("Frame of {len} bytes is too large").into());
+        }
+
+        let mut frame = vec![0; len];
+        inbound.read_exact(&mut frame).await?;
+
+        Ok(Some(frame))
+    }
+}
+
+/// The codecs a connection can negotiate with `FromClient::Hello`.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl Codec for WireFormat {
+    fn encode<P: Serialize>(&self, packet: &P) -> ChatResult<Vec<u8>> {
+        match self {
+            WireFormat::Json => JsonCodec.encode(packet),
+            WireFormat::Binary => BinaryCodec.encode(packet),
+        }
+    }
+
+    fn decode<P: DeserializeOwned>(&self, frame: &[u8]) -> ChatResult<P> {
+        match self {
+            WireFormat::Json => JsonCodec.decode(frame),
+            WireFormat::Binary => BinaryCodec.decode(frame),
+        }
+    }
+
+    async fn read_frame<S>(&self, inbound: &mut S) -> ChatResult<Option<Vec<u8>>>
+    where
+        S: AsyncBufRead + Unpin + Send,
+    {
+        match self {
+            WireFormat::Json => JsonCodec.read_frame(inbound).await,
+            WireFormat::Binary => BinaryCodec.read_frame(inbound).await,
+        }
+    }
+}
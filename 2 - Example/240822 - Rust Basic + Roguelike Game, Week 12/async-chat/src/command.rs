@@ -0,0 +1,114 @@
+use crate::{FromClient, ModAction};
+use std::sync::Arc;
+
+/// Parse a command typed by the user, like `post Dogs Woof!` or
+/// `kick Dogs jimb`. Return `None` if it isn't one.
+pub fn parse_command(line: &str) -> Option<FromClient> {
+    let (command, rest) = get_next_token(line)?;
+
+    if command == "post" {
+        let (group, rest) = get_next_token(rest)?;
+        let message = rest.trim_start().to_string();
+
+        Some(FromClient::Post {
+            group_name: Arc::new(group.to_string()),
+            message: Arc::new(message),
+        })
+    } else if command == "join" {
+        let (group, rest) = get_next_token(rest)?;
+
+        if !rest.trim_start().is_empty() {
+            return None;
+        }
+
+        Some(FromClient::Join {
+            group_name: Arc::new(group.to_string()),
+        })
+    } else if command == "typing" {
+        let (group, rest) = get_next_token(rest)?;
+
+        if !rest.trim_start().is_empty() {
+            return None;
+        }
+
+        Some(FromClient::Typing {
+            group_name: Arc::new(group.to_string()),
+        })
+    } else if command == "nick" {
+        let (nickname, rest) = get_next_token(rest)?;
+
+        if !rest.trim_start().is_empty() {
+            return None;
+        }
+
+        Some(FromClient::SetNickname {
+            nickname: Arc::new(nickname.to_string()),
+        })
+    } else if let Some(action) = parse_mod_action(command) {
+        let (group, rest) = get_next_token(rest)?;
+        let (nickname, rest) = get_next_token(rest)?;
+
+        if !rest.trim_start().is_empty() {
+            return None;
+        }
+
+        Some(FromClient::Moderate {
+            group_name: Arc::new(group.to_string()),
+            nickname: Arc::new(nickname.to_string()),
+            action,
+        })
+    } else {
+        None
+    }
+}
+
+fn parse_mod_action(command: &str) -> Option<ModAction> {
+    match command {
+        "kick" => Some(ModAction::Kick),
+        "ban" => Some(ModAction::Ban),
+        "unban" => Some(ModAction::Unban),
+        "mute" => Some(ModAction::Mute),
+        "unmute" => Some(ModAction::Unmute),
+        "owner" => Some(ModAction::TransferOwnership),
+        _ => None,
+    }
+}
+
+/// Given a string 'input', return 'Some((token, rest))',
+/// where 'token' is the first run of non-whitespace characters in 'input',
+/// and 'rest' is the rest of the string.
+/// If the string contains no non-whitespace characters, return 'None'.
+fn get_next_token(mut input: &str) -> Option<(&str, &str)> {
+    input = input.trim_start();
+
+    if input.is_empty() {
+        return None;
+    }
+
+    match input.find(char::is_whitespace) {
+        Some(space) => Some((&input[0..space], &input[space..])),
+        None => Some((input, "")),
+    }
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(
+        parse_command("post Dogs Samoyeds rock!"),
+        Some(FromClient::Post {
+            group_name: Arc::new("Dogs".to_string()),
+            message: Arc::new("Samoyeds rock!".to_string()),
+        })
+    );
+    assert_eq!(
+        parse_command("  ban Dogs jimb "),
+        Some(FromClient::Moderate {
+            group_name: Arc::new("Dogs".to_string()),
+            nickname: Arc::new("jimb".to_string()),
+            action: ModAction::Ban,
+        })
+    );
+    assert_eq!(parse_command("join Dogs Cats"), None);
+    assert_eq!(parse_command("bark"), None);
+    assert_eq!(parse_command(""), None);
+}
@@ -1,10 +1,21 @@
+use codec::WireFormat;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+pub mod backoff;
+pub mod codec;
+pub mod command;
+pub mod rt;
+pub mod server;
 pub mod utils;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum FromClient {
+    /// Sent as a JSON line before anything else to switch the connection
+    /// to another wire format. Clients that never send it keep using JSON.
+    Hello {
+        wire_format: WireFormat,
+    },
     Join {
         group_name: Arc<String>,
     },
@@ -12,17 +23,105 @@ pub enum FromClient {
         group_name: Arc<String>,
         message: Arc<String>,
     },
+    /// Choose the name this connection goes by, instead of the guest name
+    /// the server assigns. This must come before the first `Join`.
+    SetNickname {
+        nickname: Arc<String>,
+    },
+    /// Ask for the messages numbered `from` through `to`, inclusive,
+    /// for example to fill a gap in the sequence numbers.
+    Fetch {
+        group_name: Arc<String>,
+        from: u64,
+        to: u64,
+    },
+    /// Let the other members of `group_name` know we are writing something.
+    Typing {
+        group_name: Arc<String>,
+    },
+    /// Apply `action` to the user called `nickname`.
+    /// Only the owner of `group_name` may do this.
+    Moderate {
+        group_name: Arc<String>,
+        nickname: Arc<String>,
+        action: ModAction,
+    },
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// What a group's owner can do to other users.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ModAction {
+    /// Remove a member from the group. They may join again.
+    Kick,
+    /// Keep a nickname out of the group, removing its member if there is
+    /// one, who then can't come back under another nickname either.
+    /// The owner can't be banned.
+    Ban,
+    Unban,
+    /// Keep a member from posting to the group for as long as they
+    /// stay connected.
+    Mute,
+    Unmute,
+    /// Make the member the group's new owner.
+    TransferOwnership,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum FromServer {
     Message {
         group_name: Arc<String>,
+        sender: Arc<String>,
+        message: Arc<String>,
+        /// Counts up by one with each message posted to the group.
+        sequence: u64,
+        /// When the server received the message, in milliseconds since the Unix epoch.
+        timestamp: u64,
+    },
+    MemberJoined {
+        group_name: Arc<String>,
+        nickname: Arc<String>,
+    },
+    /// A member left `group_name`, was kicked, or disconnected.
+    MemberLeft {
+        group_name: Arc<String>,
+        nickname: Arc<String>,
+    },
+    Typing {
+        group_name: Arc<String>,
+        nickname: Arc<String>,
+    },
+    /// Tells a user that the owner `by` applied `action` to them. When an
+    /// owner leaves, their successor is told they were handed ownership.
+    Moderated {
+        group_name: Arc<String>,
+        action: ModAction,
+        by: Arc<String>,
+    },
+    /// Something meant for this user alone, like a server plugin's
+    /// answer to a command.
+    Notice {
+        group_name: Arc<String>,
+        from: Arc<String>,
         message: Arc<String>,
     },
+    /// The reply to `FromClient::Hello`, still sent as JSON.
+    /// Every packet after it uses the accepted format.
+    Welcome {
+        wire_format: WireFormat,
+    },
+    /// The server is going away. Nothing more will be sent on this connection.
+    ShuttingDown,
     Error(String),
 }
 
+impl FromServer {
+    /// Whether clients from before the `Hello` handshake know this packet.
+    /// They fail on anything else, so the server never sends it to them.
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, FromServer::Message { .. } | FromServer::Error(_))
+    }
+}
+
 #[test]
 fn test_from_client_json() {
     use std::sync::Arc;
@@ -43,3 +142,120 @@ fn test_from_client_json() {
         from_client
     );
 }
+
+#[cfg(test)]
+fn every_from_client() -> Vec<FromClient> {
+    vec![
+        FromClient::Hello {
+            wire_format: WireFormat::Binary,
+        },
+        FromClient::Join {
+            group_name: Arc::new("Dogs".to_string()),
+        },
+        FromClient::Post {
+            group_name: Arc::new("Dogs".to_string()),
+            message: Arc::new("Samoyeds rock!".to_string()),
+        },
+        FromClient::SetNickname {
+            nickname: Arc::new("jimb".to_string()),
+        },
+        FromClient::Fetch {
+            group_name: Arc::new("Dogs".to_string()),
+            from: 3,
+            to: 7,
+        },
+        FromClient::Typing {
+            group_name: Arc::new("Dogs".to_string()),
+        },
+        FromClient::Moderate {
+            group_name: Arc::new("Dogs".to_string()),
+            nickname: Arc::new("troll".to_string()),
+            action: ModAction::Ban,
+        },
+    ]
+}
+
+#[cfg(test)]
+fn every_from_server() -> Vec<FromServer> {
+    vec![
+        FromServer::Message {
+            group_name: Arc::new("Dogs".to_string()),
+            sender: Arc::new("jimb".to_string()),
+            message: Arc::new("Samoyeds rock!".to_string()),
+            sequence: 42,
+            timestamp: 1_724_284_800_000,
+        },
+        FromServer::MemberJoined {
+            group_name: Arc::new("Dogs".to_string()),
+            nickname: Arc::new("jimb".to_string()),
+        },
+        FromServer::MemberLeft {
+            group_name: Arc::new("Dogs".to_string()),
+            nickname: Arc::new("jimb".to_string()),
+        },
+        FromServer::Typing {
+            group_name: Arc::new("Dogs".to_string()),
+            nickname: Arc::new("jimb".to_string()),
+        },
+        FromServer::Moderated {
+            group_name: Arc::new("Dogs".to_string()),
+            action: ModAction::Mute,
+            by: Arc::new("jimb".to_string()),
+        },
+        FromServer::Notice {
+            group_name: Arc::new("Dogs".to_string()),
+            from: Arc::new("who".to_string()),
+            message: Arc::new("Members of Dogs: jimb".to_string()),
+        },
+        FromServer::Welcome {
+            wire_format: WireFormat::Json,
+        },
+        FromServer::ShuttingDown,
+        FromServer::Error("Group 'Cats' does not exist".to_string()),
+    ]
+}
+
+#[test]
+fn test_round_trip_every_packet() {
+    use codec::{BinaryCodec, Codec, JsonCodec};
+    use serde::de::DeserializeOwned;
+    use std::fmt::Debug;
+
+    fn round_trip<P>(codec: &impl Codec, packet: &P)
+    where
+        P: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let frame = codec.encode(packet).unwrap();
+        let mut inbound = futures::io::Cursor::new(frame);
+
+        let decoded = rt::block_on(utils::receive_packet(&mut inbound, codec));
+        assert_eq!(decoded.unwrap().as_ref(), Some(packet));
+
+        let end = rt::block_on(utils::receive_packet::<_, _, P>(&mut inbound, codec));
+        assert!(end.unwrap().is_none());
+    }
+
+    for packet in every_from_client() {
+        round_trip(&JsonCodec, &packet);
+        round_trip(&BinaryCodec, &packet);
+    }
+
+    for packet in every_from_server() {
+        round_trip(&JsonCodec, &packet);
+        round_trip(&BinaryCodec, &packet);
+    }
+}
+
+#[test]
+fn test_binary_frames_are_length_prefixed() {
+    use codec::{BinaryCodec, Codec};
+
+    let frame = BinaryCodec
+        .encode(&FromClient::Join {
+            group_name: Arc::new("Dogs".to_string()),
+        })
+        .unwrap();
+
+    let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+    assert_eq!(len, frame.len() - 4);
+}
@@ -0,0 +1,81 @@
+//! The async runtime, chosen at build time by a feature:
+//! `runtime-async-std` (the default) or `runtime-tokio`.
+//!
+//! The rest of the crate gets its tasks, timers and sockets from here,
+//! and does I/O through the runtime-neutral traits in `futures::io`,
+//! so it never needs to know which runtime it is running on.
+//!
+//! Channels come from `tokio::sync` in either build. They don't need
+//! the tokio runtime, so they work just as well under async-std.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+pub use tokio::sync;
+
+#[cfg(all(feature = "runtime-async-std", feature = "runtime-tokio"))]
+compile_error!("enable only one of the `runtime-async-std` and `runtime-tokio` features");
+
+#[cfg(not(any(feature = "runtime-async-std", feature = "runtime-tokio")))]
+compile_error!("enable one of the `runtime-async-std` and `runtime-tokio` features");
+
+#[cfg(feature = "runtime-async-std")]
+mod async_std_runtime;
+#[cfg(feature = "runtime-async-std")]
+use async_std_runtime as imp;
+
+#[cfg(feature = "runtime-tokio")]
+mod tokio_runtime;
+#[cfg(feature = "runtime-tokio")]
+use tokio_runtime as imp;
+
+pub use imp::{block_on, sleep, spawn, JoinHandle};
+
+pub mod net {
+    pub use super::imp::{TcpListener, TcpStream};
+    pub use std::net::SocketAddr;
+}
+
+/// Returned by `timeout` when the future didn't finish in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedOut;
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("timed out")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Run `future`, giving up on it after `duration`.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Result<F::Output, TimedOut> {
+    let expired = async {
+        sleep(duration).await;
+        Err(TimedOut)
+    };
+
+    async { Ok(future.await) }.race(expired).await
+}
+
+pub trait Race: Future + Sized {
+    /// Wait for whichever of `self` and `other` finishes first, and drop
+    /// the other. If both are ready, `self` wins.
+    fn race<F>(self, other: F) -> impl Future<Output = Self::Output>
+    where
+        F: Future<Output = Self::Output>,
+    {
+        async move {
+            let first = std::pin::pin!(self);
+            let second = std::pin::pin!(other);
+
+            futures::future::select(first, second)
+                .await
+                .factor_first()
+                .0
+        }
+    }
+}
+
+impl<F: Future> Race for F {}
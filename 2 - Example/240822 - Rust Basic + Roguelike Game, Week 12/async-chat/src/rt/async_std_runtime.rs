@@ -0,0 +1,23 @@
+//! async-std provides everything just as the crate uses it.
+
+use std::future::Future;
+use std::time::Duration;
+
+pub use async_std::net::{TcpListener, TcpStream};
+pub use async_std::task::JoinHandle;
+
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    async_std::task::block_on(future)
+}
+
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    async_std::task::spawn(future)
+}
+
+pub async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await
+}
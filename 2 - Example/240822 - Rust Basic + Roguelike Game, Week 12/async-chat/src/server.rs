@@ -0,0 +1,234 @@
+//! The chat server, as a library so that it can be started from tests
+//! as well as from the `server` binary.
+
+use crate::rt::{self, net, Race};
+use crate::utils::ChatResult;
+use std::future::Future;
+use std::sync::Arc;
+
+mod admin;
+pub mod config;
+mod connection;
+mod federation;
+mod group;
+mod group_table;
+mod metrics;
+mod nicknames;
+pub mod plugin;
+pub mod rate_limit;
+pub mod shutdown;
+
+use admin::serve_admin;
+use config::{Config, Limits};
+use connection::{serve, serve_websocket};
+use federation::Federation;
+use group_table::GroupTable;
+use nicknames::Nicknames;
+use plugin::Plugins;
+use shutdown::Shutdown;
+
+/// A server that is bound to its address, ready to `run`.
+pub struct Server {
+    listener: net::TcpListener,
+    websocket_listener: Option<net::TcpListener>,
+    federation_listener: Option<net::TcpListener>,
+    admin_listener: Option<net::TcpListener>,
+    federation: Arc<Federation>,
+    groups: Arc<GroupTable>,
+    nicknames: Arc<Nicknames>,
+    limits: Limits,
+    shutdown: Arc<Shutdown>,
+    config: Config,
+}
+
+impl Server {
+    /// Start listening on `config.address`, and on `config.websocket_address`,
+    /// `config.federation_address` and `config.admin_address` if given.
+    /// Binding to port 0 picks a free port, which `local_addr`,
+    /// `websocket_addr`, `federation_addr` and `admin_addr` then report.
+    pub async fn bind(config: Config, plugins: Plugins) -> ChatResult<Server> {
+        let listener = net::TcpListener::bind(&config.address).await?;
+        let websocket_listener = bind_optional(&config.websocket_address).await?;
+        let federation_listener = bind_optional(&config.federation_address).await?;
+        let admin_listener = bind_optional(&config.admin_address).await?;
+        let nicknames = Nicknames::new();
+
+        for name in plugins.names() {
+            nicknames.reserve(name);
+        }
+
+        let groups = Arc::new(GroupTable::new(
+            config.limits.group,
+            config.history,
+            config.idle_timeout,
+            plugins,
+        ));
+        let shutdown = Arc::new(Shutdown::new());
+        let federation = Arc::new(Federation::new(
+            Arc::new(config.name.clone()),
+            groups.clone(),
+            shutdown.clone(),
+        ));
+
+        Ok(Server {
+            listener,
+            websocket_listener,
+            federation_listener,
+            admin_listener,
+            federation,
+            groups,
+            nicknames: Arc::new(nicknames),
+            limits: config.limits,
+            shutdown,
+            config,
+        })
+    }
+
+    pub fn local_addr(&self) -> ChatResult<net::SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn websocket_addr(&self) -> ChatResult<Option<net::SocketAddr>> {
+        local_addr_optional(&self.websocket_listener)
+    }
+
+    pub fn federation_addr(&self) -> ChatResult<Option<net::SocketAddr>> {
+        local_addr_optional(&self.federation_listener)
+    }
+
+    pub fn admin_addr(&self) -> ChatResult<Option<net::SocketAddr>> {
+        local_addr_optional(&self.admin_listener)
+    }
+
+    /// The handle used to stop the server once it is running.
+    pub fn shutdown(&self) -> Arc<Shutdown> {
+        self.shutdown.clone()
+    }
+
+    /// Serve connections until a shutdown is requested, then give them
+    /// `config.shutdown_grace` to finish.
+    pub async fn run(self) -> ChatResult<()> {
+        for peer in &self.config.peers {
+            let guard = self.shutdown.track_connection();
+            let dialing = self.federation.clone().dial(peer.clone());
+
+            rt::spawn(async move {
+                dialing.await;
+                drop(guard);
+            });
+        }
+
+        // Both kinds of client connection share the same groups and
+        // nicknames, so messages cross freely between them.
+        let tcp = self.accept(&self.listener, |socket| {
+            serve(
+                socket,
+                self.groups.clone(),
+                self.nicknames.clone(),
+                self.limits,
+                self.shutdown.clone(),
+            )
+        });
+        let websocket = self.accept_optional(&self.websocket_listener, |socket| {
+            serve_websocket(
+                socket,
+                self.groups.clone(),
+                self.nicknames.clone(),
+                self.limits,
+                self.shutdown.clone(),
+            )
+        });
+        let federation = self.accept_optional(&self.federation_listener, |socket| {
+            self.federation.clone().link(socket)
+        });
+
+        let admin = self.accept_optional(&self.admin_listener, |socket| {
+            serve_admin(socket, self.groups.clone(), self.shutdown.clone())
+        });
+
+        futures::future::try_join4(tcp, websocket, federation, admin).await?;
+
+        // Stop accepting, then give the connections a chance to deliver
+        // everything that has been posted and say goodbye.
+        drop(self.listener);
+        drop(self.websocket_listener);
+        drop(self.federation_listener);
+        drop(self.admin_listener);
+
+        eprintln!("Shutting down...");
+
+        let finished = self.shutdown.connections_finished();
+        if rt::timeout(self.config.shutdown_grace, finished)
+            .await
+            .is_err()
+        {
+            eprintln!("Some connections did not finish in time");
+        }
+
+        Ok(())
+    }
+
+    /// Hand each connection to `listener` to `serve`, until a shutdown
+    /// is requested.
+    async fn accept<S, F>(&self, listener: &net::TcpListener, serve: S) -> ChatResult<()>
+    where
+        S: Fn(net::TcpStream) -> F,
+        F: Future<Output = ChatResult<()>> + Send + 'static,
+    {
+        loop {
+            let accepted = async { Some(listener.accept().await) };
+            let stop = async {
+                self.shutdown.requested().await;
+                None
+            };
+
+            let (socket, _address) = match accepted.race(stop).await {
+                Some(accepted) => accepted?,
+                None => return Ok(()),
+            };
+
+            let guard = self.shutdown.track_connection();
+            let connection = serve(socket);
+
+            rt::spawn(async move {
+                log_error(connection.await);
+                drop(guard);
+            });
+        }
+    }
+
+    async fn accept_optional<S, F>(
+        &self,
+        listener: &Option<net::TcpListener>,
+        serve: S,
+    ) -> ChatResult<()>
+    where
+        S: Fn(net::TcpStream) -> F,
+        F: Future<Output = ChatResult<()>> + Send + 'static,
+    {
+        match listener {
+            Some(listener) => self.accept(listener, serve).await,
+            None => Ok(()),
+        }
+    }
+}
+
+async fn bind_optional(address: &Option<String>) -> ChatResult<Option<net::TcpListener>> {
+    match address {
+        Some(address) => Ok(Some(net::TcpListener::bind(address).await?)),
+        None => Ok(None),
+    }
+}
+
+fn local_addr_optional(listener: &Option<net::TcpListener>) -> ChatResult<Option<net::SocketAddr>> {
+    match listener {
+        Some(listener) => Ok(Some(listener.local_addr()?)),
+        None => Ok(None),
+    }
+}
+
+fn log_error(result: ChatResult<()>) {
+    if let Err(error) = result {
+        eprintln!("Error: {error}");
+    }
+}
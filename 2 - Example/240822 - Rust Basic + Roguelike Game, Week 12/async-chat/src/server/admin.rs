@@ -0,0 +1,90 @@
+//! A small HTTP listener that reports the server's metrics, for people
+//! and monitoring tools. It has no authentication, so it should only be
+//! reachable from trusted machines.
+//!
+//! - `GET /metrics` answers in the Prometheus text format.
+//! - `GET /metrics.json` answers with the same numbers as JSON.
+
+use crate::rt::net::TcpStream;
+use crate::rt::Race;
+use crate::server::group_table::GroupTable;
+use crate::server::shutdown::Shutdown;
+use crate::utils::ChatResult;
+use futures::io::BufReader;
+use futures::prelude::*;
+use std::sync::Arc;
+
+/// Requests are a line and a few headers; anything longer is not for us.
+const MAX_REQUEST: u64 = 8 * 1024;
+
+pub async fn serve_admin(
+    socket: TcpStream,
+    groups: Arc<GroupTable>,
+    shutdown: Arc<Shutdown>,
+) -> ChatResult<()> {
+    // A client that never finishes its request mustn't hold up a shutdown.
+    answer(socket, &groups)
+        .race(async {
+            shutdown.requested().await;
+            Ok(())
+        })
+        .await
+}
+
+async fn answer(socket: TcpStream, groups: &GroupTable) -> ChatResult<()> {
+    let mut to_client = socket.clone();
+    let mut from_client = BufReader::new(socket).take(MAX_REQUEST);
+
+    let mut request_line = String::new();
+    from_client.read_line(&mut request_line).await?;
+
+    // Skip the headers; none of them change the answer.
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if from_client.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let snapshot = groups.metrics().snapshot(groups);
+            (
+                "200 OK",
+                "text/plain; version=0.0.4",
+                snapshot.to_prometheus(),
+            )
+        }
+        (Some("GET"), Some("/metrics.json")) => {
+            let snapshot = groups.metrics().snapshot(groups);
+            (
+                "200 OK",
+                "application/json",
+                serde_json::to_string(&snapshot)?,
+            )
+        }
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Only GET is supported\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+
+    to_client.write_all(response.as_bytes()).await?;
+    to_client.flush().await?;
+
+    Ok(())
+}
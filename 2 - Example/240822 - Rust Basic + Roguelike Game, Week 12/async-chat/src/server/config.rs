@@ -0,0 +1,156 @@
+use crate::server::rate_limit::RateLimit;
+use std::time::Duration;
+
+/// Server settings, taken from the command line.
+pub struct Config {
+    pub address: String,
+    /// Where to accept WebSocket connections from browsers, if anywhere.
+    pub websocket_address: Option<String>,
+    /// What this server calls itself to its peers. Users on other servers
+    /// see our users as `nickname@name`.
+    pub name: String,
+    /// Where to accept links from peer servers, if anywhere.
+    pub federation_address: Option<String>,
+    /// The federation addresses of the peers to link up with.
+    pub peers: Vec<String>,
+    /// Where to serve metrics over HTTP, if anywhere.
+    pub admin_address: Option<String>,
+    pub limits: Limits,
+    /// How many recent messages each group keeps for `FromClient::Fetch`.
+    pub history: usize,
+    /// How long a group with no subscribers is kept before it is removed.
+    pub idle_timeout: Duration,
+    /// How long to wait for connections to finish when shutting down.
+    pub shutdown_grace: Duration,
+}
+
+/// Flood protection settings.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// How fast a single connection may post, across all groups.
+    pub connection: RateLimit,
+    /// How fast a single group accepts posts, across all connections.
+    pub group: RateLimit,
+    /// How many rejected posts a connection gets before it is dropped.
+    pub max_strikes: u32,
+    /// How long it takes for one strike to be forgiven.
+    pub forgive_after: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            connection: RateLimit {
+                per_second: 5.0,
+                burst: 10,
+            },
+            group: RateLimit {
+                per_second: 50.0,
+                burst: 100,
+            },
+            max_strikes: 5,
+            forgive_after: Duration::from_secs(10),
+        }
+    }
+}
+
+pub const USAGE: &str = "\
+Usage: server ADDRESS [OPTIONS]
+
+Options:
+    --websocket ADDR   also accept WebSocket connections on ADDR
+    --name NAME        what to call this server on other servers
+    --federate ADDR    accept links from other servers on ADDR
+    --peer ADDR        link up with the server federating on ADDR
+    --admin ADDR       serve metrics over HTTP on ADDR
+    --post-rate N      posts per second allowed per connection
+    --post-burst N     posts a connection may send at once
+    --group-rate N     posts per second allowed per group
+    --group-burst N    posts a group accepts at once
+    --max-strikes N    rejected posts before a connection is dropped
+    --history N        recent messages each group keeps for fetching
+    --idle-timeout N   seconds an empty group is kept before it is removed
+    --shutdown-grace N seconds to let connections finish when shutting down";
+
+impl Config {
+    /// The default settings for a server listening on `address`.
+    pub fn new(address: impl Into<String>) -> Config {
+        let address = address.into();
+
+        Config {
+            name: address.clone(),
+            address,
+            websocket_address: None,
+            federation_address: None,
+            peers: Vec::new(),
+            admin_address: None,
+            limits: Limits::default(),
+            history: 1000,
+            idle_timeout: Duration::ZERO,
+            shutdown_grace: Duration::from_secs(5),
+        }
+    }
+
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+        let mut config = Config::new(args.next().ok_or("Missing ADDRESS")?);
+        let limits = &mut config.limits;
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {flag}"))?;
+
+            match flag.as_str() {
+                "--websocket" => config.websocket_address = Some(value),
+                "--name" if value.contains('@') => {
+                    return Err("--name may not contain '@'".to_string());
+                }
+                "--name" => config.name = value,
+                "--federate" => config.federation_address = Some(value),
+                "--peer" => config.peers.push(value),
+                "--admin" => config.admin_address = Some(value),
+                "--post-rate" => limits.connection.per_second = rate(&flag, &value)?,
+                "--post-burst" => limits.connection.burst = parse(&flag, &value)?,
+                "--group-rate" => limits.group.per_second = rate(&flag, &value)?,
+                "--group-burst" => limits.group.burst = parse(&flag, &value)?,
+                "--max-strikes" => limits.max_strikes = parse(&flag, &value)?,
+                "--history" => match parse(&flag, &value)? {
+                    0 => return Err("--history must be at least 1".to_string()),
+                    history => config.history = history,
+                },
+                "--idle-timeout" => {
+                    config.idle_timeout = seconds(&flag, &value)?;
+                }
+                "--shutdown-grace" => {
+                    config.shutdown_grace = seconds(&flag, &value)?;
+                }
+                _ => return Err(format!("Unknown option {flag}")),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {flag}: {value:?}"))
+}
+
+/// Parse a number of seconds, which may have a fraction.
+fn seconds(flag: &str, value: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(parse(flag, value)?)
+        .map_err(|_| format!("{flag} must be a number of seconds, not {value:?}"))
+}
+
+/// Parse a rate per second. A bucket that never refills, or refills
+/// infinitely fast, isn't a limit.
+fn rate(flag: &str, value: &str) -> Result<f64, String> {
+    match parse(flag, value)? {
+        rate if f64::is_finite(rate) && rate > 0.0 => Ok(rate),
+        _ => Err(format!(
+            "{flag} must be a positive number of posts per second, not {value:?}"
+        )),
+    }
+}
@@ -0,0 +1,480 @@
+use crate::codec::{Codec, JsonCodec, WireFormat};
+use crate::rt::net::TcpStream;
+use crate::rt::sync::{watch, Mutex};
+use crate::rt::Race;
+use crate::utils::{self, ChatResult};
+use crate::{FromClient, FromServer};
+use async_tungstenite::tungstenite::Message;
+use async_tungstenite::{WebSocketReceiver, WebSocketSender};
+use futures::io::BufReader;
+use futures::prelude::*;
+use std::sync::Arc;
+
+use crate::server::config::Limits;
+use crate::server::group::{Group, Identity};
+use crate::server::group_table::GroupTable;
+use crate::server::metrics::ConnectionGuard;
+use crate::server::nicknames::Nicknames;
+use crate::server::plugin::Reply;
+use crate::server::rate_limit::{RateLimit, Strikes, TokenBucket};
+use crate::server::shutdown::Shutdown;
+
+/// Typing indicators are only hints, so rather than counting against the
+/// post limit, extra ones are quietly dropped.
+const TYPING_LIMIT: RateLimit = RateLimit {
+    per_second: 1.0,
+    burst: 3,
+};
+
+/// A fetch can return a whole group's history, so clients only get a few.
+const FETCH_LIMIT: RateLimit = RateLimit {
+    per_second: 1.0,
+    burst: 5,
+};
+
+pub async fn serve(
+    socket: TcpStream,
+    groups: Arc<GroupTable>,
+    nicknames: Arc<Nicknames>,
+    limits: Limits,
+    shutdown: Arc<Shutdown>,
+) -> ChatResult<()> {
+    let mut from_client = BufReader::new(socket.clone());
+    let (wire_format, first_request) = handshake(&socket, &mut from_client).await?;
+    let legacy = wire_format.is_none();
+    let wire_format = wire_format.unwrap_or_default();
+    let outbound = Arc::new(Outbound::new(socket, wire_format, legacy));
+    let inbound = Inbound::Tcp {
+        from_client,
+        wire_format,
+    };
+
+    let session = Session::new(outbound, groups, nicknames, limits);
+    session.serve(inbound, first_request, &shutdown).await
+}
+
+/// Serve a browser client. WebSocket connections skip the handshake:
+/// every message in either direction is one packet as JSON text.
+pub async fn serve_websocket(
+    socket: TcpStream,
+    groups: Arc<GroupTable>,
+    nicknames: Arc<Nicknames>,
+    limits: Limits,
+    shutdown: Arc<Shutdown>,
+) -> ChatResult<()> {
+    let (to_client, from_client) = async_tungstenite::accept_async(socket).await?.split();
+    let outbound = Arc::new(Outbound::websocket(to_client));
+    let inbound = Inbound::WebSocket(from_client);
+
+    let session = Session::new(outbound.clone(), groups, nicknames, limits);
+    let result = session.serve(inbound, None, &shutdown).await;
+    outbound.close_websocket().await;
+
+    result
+}
+
+/// The state of one client's connection.
+struct Session {
+    nickname: Arc<String>,
+    identity: Identity,
+    has_joined: bool,
+    outbound: Arc<Outbound>,
+    groups: Arc<GroupTable>,
+    nicknames: Arc<Nicknames>,
+    posts: TokenBucket,
+    typing: TokenBucket,
+    fetches: TokenBucket,
+    strikes: Strikes,
+    /// Counts this connection in the metrics for as long as it lasts.
+    _counted: ConnectionGuard,
+}
+
+impl Session {
+    fn new(
+        outbound: Arc<Outbound>,
+        groups: Arc<GroupTable>,
+        nicknames: Arc<Nicknames>,
+        limits: Limits,
+    ) -> Session {
+        let counted = groups.metrics().track_connection();
+
+        Session {
+            nickname: nicknames.guest(),
+            identity: Identity::unique(),
+            has_joined: false,
+            outbound,
+            groups,
+            nicknames,
+            posts: TokenBucket::new(limits.connection),
+            typing: TokenBucket::new(TYPING_LIMIT),
+            fetches: TokenBucket::new(FETCH_LIMIT),
+            strikes: Strikes::new(limits.max_strikes, limits.forgive_after),
+            _counted: counted,
+        }
+    }
+
+    /// Handle requests until the client leaves or the server shuts down,
+    /// then wind the connection down.
+    async fn serve(
+        mut self,
+        mut inbound: Inbound,
+        first_request: Option<FromClient>,
+        shutdown: &Shutdown,
+    ) -> ChatResult<()> {
+        let result = self
+            .run(&mut inbound, first_request)
+            .race(async {
+                // Stop taking requests, but finish delivering what's been posted.
+                shutdown.requested().await;
+                Ok(())
+            })
+            .await;
+
+        // Let this connection's subscriptions end, so that empty groups
+        // can be reclaimed right away.
+        self.outbound.close();
+        self.outbound.drained().await;
+        self.nicknames.release(&self.nickname);
+
+        if shutdown.is_requested() {
+            // The client may be gone already, and there is nothing left to do about it.
+            let _ = self.outbound.send(FromServer::ShuttingDown).await;
+        }
+
+        result
+    }
+
+    async fn run(
+        &mut self,
+        inbound: &mut Inbound,
+        first_request: Option<FromClient>,
+    ) -> ChatResult<()> {
+        let mut next_request = match first_request {
+            Some(request) => Some(request),
+            None => inbound.receive().await?,
+        };
+
+        while let Some(request) = next_request {
+            self.handle(request).await?;
+            next_request = inbound.receive().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Carry out one request, reporting any problem with it to the client.
+    /// Return an error only if the connection should be dropped.
+    async fn handle(&mut self, request: FromClient) -> ChatResult<()> {
+        let result = match request {
+            FromClient::Hello { .. } => {
+                Err("The wire format can only be chosen by the first request".to_string())
+            }
+            FromClient::SetNickname { nickname } => self.set_nickname(nickname),
+            FromClient::Join { group_name } => {
+                self.has_joined = true;
+
+                let nickname = self.nickname.clone();
+                match self
+                    .groups
+                    .join(group_name, nickname, self.identity, self.outbound.clone())
+                {
+                    Ok(group) => {
+                        let replies = self.groups.plugins().join(&group, &self.nickname);
+                        self.deliver(&group, replies).await?;
+                        Ok(())
+                    }
+                    Err(message) => Err(message),
+                }
+            }
+            FromClient::Post {
+                group_name,
+                message,
+            } => match self.groups.get(&group_name) {
+                Some(_) if !self.posts.try_take() => {
+                    if !self.strikes.strike() {
+                        let report = FromServer::Error("Disconnected for flooding".to_string());
+                        self.outbound.send(report).await?;
+
+                        return Err("Client disconnected for flooding".into());
+                    }
+
+                    Err("You are posting too fast; message dropped".to_string())
+                }
+                Some(group) => match group.check_post(&self.nickname, &self.identity) {
+                    Ok(()) => {
+                        self.post(&group, message).await?;
+                        Ok(())
+                    }
+                    Err(message) => Err(message),
+                },
+                None => Err(format!("Group '{group_name}' does not exist")),
+            },
+            FromClient::Fetch {
+                group_name,
+                from,
+                to,
+            } => match self.groups.get(&group_name) {
+                Some(_) if !self.fetches.try_take() => {
+                    Err("You are fetching too often; try again later".to_string())
+                }
+                Some(group) => self.fetch(&group, from, to).await?,
+                None => Err(format!("Group '{group_name}' does not exist")),
+            },
+            FromClient::Typing { group_name } => match self.groups.get(&group_name) {
+                Some(_) if !self.typing.try_take() => Ok(()),
+                Some(group) => group.typing(&self.nickname, &self.identity),
+                None => Err(format!("Group '{group_name}' does not exist")),
+            },
+            FromClient::Moderate {
+                group_name,
+                nickname,
+                action,
+            } => match self.groups.get(&group_name) {
+                Some(group) => match group.moderate(&self.identity, &nickname, action) {
+                    Ok(Some(target)) => {
+                        let notice = FromServer::Moderated {
+                            group_name,
+                            action,
+                            by: self.nickname.clone(),
+                        };
+
+                        // The target may be disconnecting; that is their problem, not ours.
+                        let _ = target.send(notice).await;
+                        Ok(())
+                    }
+                    Ok(None) => Ok(()),
+                    Err(message) => Err(message),
+                },
+                None => Err(format!("Group '{group_name}' does not exist")),
+            },
+        };
+
+        if let Err(message) = result {
+            let report = FromServer::Error(message);
+            self.outbound.send(report).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Run a post past the plugins, and deliver whatever is left of it
+    /// along with their replies.
+    async fn post(&self, group: &Group, message: Arc<String>) -> ChatResult<()> {
+        let (message, replies) = self.groups.plugins().post(group, &self.nickname, message);
+
+        if let Some(message) = message {
+            group.post(self.nickname.clone(), message);
+        }
+
+        self.deliver(group, replies).await
+    }
+
+    async fn deliver(&self, group: &Group, replies: Vec<Reply>) -> ChatResult<()> {
+        for reply in replies {
+            match reply {
+                Reply::Private { from, message } => {
+                    let notice = FromServer::Notice {
+                        group_name: group.name().clone(),
+                        from,
+                        message: Arc::new(message),
+                    };
+
+                    self.outbound.send(notice).await?;
+                }
+                Reply::Group { from, message } => group.post(from, Arc::new(message)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send the client the messages it asked for. If some of them have
+    /// already dropped out of the group's history, say so.
+    async fn fetch(&self, group: &Group, from: u64, to: u64) -> ChatResult<Result<(), String>> {
+        let (messages, oldest) = match group.fetch(&self.nickname, from, to) {
+            Ok(fetched) => fetched,
+            Err(message) => return Ok(Err(message)),
+        };
+
+        for message in messages {
+            self.outbound.send(message).await?;
+        }
+
+        if from < oldest {
+            let last_missing = to.min(oldest - 1);
+            return Ok(Err(format!(
+                "Messages {from} to {last_missing} of '{}' are no longer available",
+                group.name()
+            )));
+        }
+
+        Ok(Ok(()))
+    }
+
+    fn set_nickname(&mut self, nickname: Arc<String>) -> Result<(), String> {
+        if self.has_joined {
+            return Err("Choose a nickname before joining any group".to_string());
+        }
+
+        self.nicknames.claim(&nickname)?;
+        self.nicknames.release(&self.nickname);
+        self.nickname = nickname;
+
+        Ok(())
+    }
+}
+
+/// Read the first request, which is always JSON.
+///
+/// If it is a `Hello`, accept the requested wire format and acknowledge it
+/// with a `Welcome`. Otherwise this is a client that predates the handshake:
+/// return no wire format, meaning JSON limited to the packets such clients
+/// understand, and hand the request back so it can be served as usual.
+async fn handshake(
+    mut socket: &TcpStream,
+    from_client: &mut BufReader<TcpStream>,
+) -> ChatResult<(Option<WireFormat>, Option<FromClient>)> {
+    match utils::receive_packet(from_client, &JsonCodec).await? {
+        Some(FromClient::Hello { wire_format }) => {
+            let welcome = FromServer::Welcome { wire_format };
+            utils::send_packet(&mut socket, &JsonCodec, &welcome).await?;
+            socket.flush().await?;
+
+            Ok((Some(wire_format), None))
+        }
+        first_request => Ok((None, first_request)),
+    }
+}
+
+/// Where a connection's requests come from.
+enum Inbound {
+    Tcp {
+        from_client: BufReader<TcpStream>,
+        wire_format: WireFormat,
+    },
+    WebSocket(WebSocketReceiver<TcpStream>),
+}
+
+impl Inbound {
+    /// Return the next request, or `None` once the client has left.
+    async fn receive(&mut self) -> ChatResult<Option<FromClient>> {
+        let from_client = match self {
+            Inbound::Tcp {
+                from_client,
+                wire_format,
+            } => return utils::receive_packet(from_client, wire_format).await,
+            Inbound::WebSocket(from_client) => from_client,
+        };
+
+        while let Some(message) = from_client.next().await {
+            match message? {
+                Message::Text(text) => return Ok(Some(JsonCodec.decode(text.as_bytes())?)),
+                Message::Binary(bytes) => return Ok(Some(JsonCodec.decode(&bytes)?)),
+                Message::Close(_) => break,
+                // Pings are answered by the WebSocket library itself.
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Where a connection's packets go.
+enum ToClient {
+    Tcp(TcpStream),
+    WebSocket(WebSocketSender<TcpStream>),
+}
+
+pub struct Outbound {
+    to_client: Mutex<ToClient>,
+    wire_format: WireFormat,
+    /// Whether the client skipped the handshake, and so only understands
+    /// the packets from before it existed.
+    legacy: bool,
+    closed: watch::Sender<bool>,
+    /// How many group subscriptions are still forwarding to this connection.
+    subscriptions: watch::Sender<usize>,
+}
+
+impl Outbound {
+    pub fn new(to_client: TcpStream, wire_format: WireFormat, legacy: bool) -> Outbound {
+        Outbound::with(ToClient::Tcp(to_client), wire_format, legacy)
+    }
+
+    fn websocket(to_client: WebSocketSender<TcpStream>) -> Outbound {
+        Outbound::with(ToClient::WebSocket(to_client), WireFormat::Json, false)
+    }
+
+    fn with(to_client: ToClient, wire_format: WireFormat, legacy: bool) -> Outbound {
+        Outbound {
+            to_client: Mutex::new(to_client),
+            wire_format,
+            legacy,
+            closed: watch::Sender::new(false),
+            subscriptions: watch::Sender::new(0),
+        }
+    }
+
+    /// End a WebSocket connection with a proper close handshake,
+    /// instead of just dropping the socket.
+    async fn close_websocket(&self) {
+        if let ToClient::WebSocket(to_client) = &mut *self.to_client.lock().await {
+            let _ = to_client.close(None).await;
+        }
+    }
+
+    /// Mark the connection as finished.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// Wait until `close` has been called.
+    pub async fn closed(&self) {
+        let mut closed = self.closed.subscribe();
+        let _ = closed.wait_for(|closed| *closed).await;
+    }
+
+    /// Count a group subscription as forwarding to this connection
+    /// until the returned guard is dropped.
+    pub fn track_subscription(&self) -> SubscriptionGuard {
+        self.subscriptions.send_modify(|count| *count += 1);
+
+        SubscriptionGuard(self.subscriptions.clone())
+    }
+
+    /// Wait until every subscription has finished sending. Subscriptions
+    /// deliver whatever they have already received once `close` is called,
+    /// so after this nothing more is going to be sent.
+    pub async fn drained(&self) {
+        let mut subscriptions = self.subscriptions.subscribe();
+        let _ = subscriptions.wait_for(|count| *count == 0).await;
+    }
+
+    pub async fn send(&self, packet: FromServer) -> ChatResult<()> {
+        if self.legacy && !packet.is_legacy() {
+            return Ok(());
+        }
+
+        match &mut *self.to_client.lock().await {
+            ToClient::Tcp(to_client) => {
+                utils::send_packet(to_client, &self.wire_format, &packet).await?;
+                to_client.flush().await?;
+            }
+            ToClient::WebSocket(to_client) => {
+                let json = serde_json::to_string(&packet)?;
+                to_client.send(Message::text(json)).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SubscriptionGuard(watch::Sender<usize>);
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
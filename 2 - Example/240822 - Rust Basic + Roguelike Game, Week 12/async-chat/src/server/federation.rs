@@ -0,0 +1,290 @@
+//! Links between servers, so that a group on one server mirrors the group
+//! of the same name on the others.
+//!
+//! Each server only passes on the posts its own users made, so a message
+//! crosses a link at most once and can never come back around. The price
+//! is that messages don't hop: servers that should share groups each need
+//! a link of their own to every other.
+
+use crate::backoff::Backoff;
+use crate::codec::WireFormat;
+use crate::rt::net::TcpStream;
+use crate::rt::sync::broadcast;
+use crate::rt::sync::broadcast::error::RecvError;
+use crate::rt::sync::watch;
+use crate::rt::{self, Race};
+use crate::server::group_table::GroupTable;
+use crate::server::shutdown::Shutdown;
+use crate::utils::{self, ChatResult};
+use futures::io::BufReader;
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Peers always talk to each other in the compact format.
+const WIRE_FORMAT: WireFormat = WireFormat::Binary;
+
+/// A post made on one server, on its way to the others.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Relayed {
+    pub group_name: Arc<String>,
+    pub sender: Arc<String>,
+    pub message: Arc<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum PeerPacket {
+    /// The first packet each side of a link sends.
+    Hello {
+        server: Arc<String>,
+    },
+    Post(Relayed),
+}
+
+pub struct Federation {
+    name: Arc<String>,
+    groups: Arc<GroupTable>,
+    shutdown: Arc<Shutdown>,
+    /// The link to each peer, so that we never keep two.
+    linked: Mutex<HashMap<Arc<String>, Link>>,
+    /// Notified whenever a link ends, for dialers waiting their turn.
+    unlinked: watch::Sender<()>,
+}
+
+/// A link that is up.
+struct Link {
+    /// Tells this link apart from a later one to the same peer.
+    id: u64,
+    /// Whether this is the link to keep when there are two.
+    preferred: bool,
+    /// Set to end the link, when the one to keep turns up.
+    superseded: watch::Sender<bool>,
+}
+
+/// A link whose two sides have said hello.
+struct Greeted {
+    peer: Arc<String>,
+    relayed: broadcast::Receiver<Relayed>,
+    to_peer: TcpStream,
+    from_peer: BufReader<TcpStream>,
+}
+
+static NEXT_LINK_ID: AtomicU64 = AtomicU64::new(0);
+
+impl Federation {
+    pub fn new(name: Arc<String>, groups: Arc<GroupTable>, shutdown: Arc<Shutdown>) -> Federation {
+        Federation {
+            name,
+            groups,
+            shutdown,
+            linked: Mutex::new(HashMap::new()),
+            unlinked: watch::Sender::new(()),
+        }
+    }
+
+    /// Keep a link to the peer at `address` up until the server shuts down,
+    /// reconnecting whenever it drops.
+    pub async fn dial(self: Arc<Self>, address: String) {
+        let mut backoff = Backoff::default();
+        // Who answers at `address`, once we know.
+        let mut peer = None;
+
+        while !self.shutdown.is_requested() {
+            // If the peer dialed us too and its link is the one to keep,
+            // dialing again would only be turned away.
+            if let Some(peer) = &peer {
+                self.unlinked_from(peer)
+                    .race(self.shutdown.requested())
+                    .await;
+            }
+
+            match TcpStream::connect(&address).await {
+                // A link that ends cleanly was working, so try again soon.
+                Ok(socket) => {
+                    let result = match self.greet(socket).await {
+                        Ok(Some(greeted)) => {
+                            peer = Some(greeted.peer.clone());
+                            self.clone().relay(greeted, true).await
+                        }
+                        Ok(None) => Ok(()),
+                        Err(error) => Err(error),
+                    };
+
+                    match result {
+                        Ok(()) => backoff.reset(),
+                        Err(error) => eprintln!("Link to peer {address} failed: {error}"),
+                    }
+                }
+                Err(error) => eprintln!("Can't reach peer {address}: {error}"),
+            }
+
+            rt::sleep(backoff.next_delay())
+                .race(self.shutdown.requested())
+                .await;
+        }
+    }
+
+    /// Relay posts over `socket`, which a peer dialed, in both directions
+    /// until either side goes away or the server shuts down.
+    pub async fn link(self: Arc<Self>, socket: TcpStream) -> ChatResult<()> {
+        match self.greet(socket).await? {
+            Some(greeted) => self.relay(greeted, false).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Exchange hellos over `socket`. Return `None` if the peer hung up
+    /// first. This is the same whichever side opened the link.
+    async fn greet(&self, socket: TcpStream) -> ChatResult<Option<Greeted>> {
+        let mut to_peer = socket.clone();
+        let mut from_peer = BufReader::new(socket);
+
+        // Subscribe before saying hello, so nothing posted once the peer
+        // knows about us can be missed.
+        let relayed = self.groups.relayed();
+
+        let hello = PeerPacket::Hello {
+            server: self.name.clone(),
+        };
+        utils::send_packet(&mut to_peer, &WIRE_FORMAT, &hello).await?;
+        to_peer.flush().await?;
+
+        let peer = match utils::receive_packet(&mut from_peer, &WIRE_FORMAT).await? {
+            Some(PeerPacket::Hello { server }) => server,
+            Some(_) => return Err("Peer did not say hello".into()),
+            None => return Ok(None),
+        };
+
+        if peer == self.name {
+            return Err(format!("Peer is also called '{peer}'").into());
+        }
+
+        Ok(Some(Greeted {
+            peer,
+            relayed,
+            to_peer,
+            from_peer,
+        }))
+    }
+
+    /// Relay posts over a link in both directions until either side goes
+    /// away, the server shuts down, or a link to keep instead turns up.
+    ///
+    /// When two servers dial each other at once, each ends up with two
+    /// links to the other. Both sides keep the one dialed by the server
+    /// whose name sorts first, so they always agree on which that is.
+    async fn relay(self: Arc<Self>, greeted: Greeted, dialed: bool) -> ChatResult<()> {
+        let Greeted {
+            peer,
+            relayed,
+            to_peer,
+            from_peer,
+        } = greeted;
+
+        let id = NEXT_LINK_ID.fetch_add(1, Ordering::Relaxed);
+        let preferred = dialed == (self.name < peer);
+        let (superseded, mut superseded_receiver) = watch::channel(false);
+
+        {
+            let mut linked = self.linked.lock().unwrap();
+
+            match linked.get(&peer) {
+                None => {}
+                Some(existing) if existing.preferred == preferred => {
+                    return Err(format!("Already linked to '{peer}'").into());
+                }
+                // The peer keeps the other link too, and closes this one.
+                Some(existing) if existing.preferred => return Ok(()),
+                Some(existing) => {
+                    existing.superseded.send_replace(true);
+                }
+            }
+
+            linked.insert(
+                peer.clone(),
+                Link {
+                    id,
+                    preferred,
+                    superseded,
+                },
+            );
+        }
+
+        let result = self
+            .relay_to(&peer, relayed, to_peer)
+            .race(self.relay_from(&peer, from_peer))
+            .race(async {
+                self.shutdown.requested().await;
+                Ok(())
+            })
+            .race(async {
+                let _ = superseded_receiver.wait_for(|superseded| *superseded).await;
+                Ok(())
+            })
+            .await;
+
+        {
+            let mut linked = self.linked.lock().unwrap();
+            if linked.get(&peer).is_some_and(|link| link.id == id) {
+                linked.remove(&peer);
+            }
+        }
+        self.unlinked.send_replace(());
+
+        result
+    }
+
+    /// Wait until we have no link to `peer`.
+    async fn unlinked_from(&self, peer: &Arc<String>) {
+        let mut unlinked = self.unlinked.subscribe();
+        let _ = unlinked
+            .wait_for(|_| !self.linked.lock().unwrap().contains_key(peer))
+            .await;
+    }
+
+    /// Send every post made on this server to `peer`.
+    async fn relay_to(
+        &self,
+        peer: &str,
+        mut relayed: broadcast::Receiver<Relayed>,
+        mut to_peer: TcpStream,
+    ) -> ChatResult<()> {
+        loop {
+            let post = match relayed.recv().await {
+                Ok(post) => post,
+                Err(RecvError::Lagged(skipped)) => {
+                    self.groups.metrics().lagged(skipped);
+                    eprintln!("Link to '{peer}' fell behind; {skipped} posts were not relayed");
+                    continue;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            };
+
+            utils::send_packet(&mut to_peer, &WIRE_FORMAT, &PeerPacket::Post(post)).await?;
+            to_peer.flush().await?;
+        }
+    }
+
+    /// Deliver every post `peer` sends us.
+    async fn relay_from(&self, peer: &str, mut from_peer: BufReader<TcpStream>) -> ChatResult<()> {
+        while let Some(packet) = utils::receive_packet(&mut from_peer, &WIRE_FORMAT).await? {
+            match packet {
+                PeerPacket::Post(post) => self.deliver(peer, post),
+                PeerPacket::Hello { .. } => return Err("Peer said hello twice".into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Post a message from a peer's user to our group of the same name.
+    /// If nobody here has joined that group, there is nobody to tell.
+    fn deliver(&self, peer: &str, post: Relayed) {
+        if let Some(group) = self.groups.get(&post.group_name) {
+            let sender = Arc::new(format!("{}@{peer}", post.sender));
+            group.post_relayed(sender, post.message);
+        }
+    }
+}
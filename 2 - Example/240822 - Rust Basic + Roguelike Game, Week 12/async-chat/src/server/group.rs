@@ -0,0 +1,537 @@
+use crate::rt;
+use crate::rt::sync::broadcast;
+use crate::rt::sync::broadcast::error::{RecvError, TryRecvError};
+use crate::rt::sync::watch;
+use crate::rt::Race;
+use crate::server::connection::Outbound;
+use crate::server::federation::Relayed;
+use crate::server::metrics::Metrics;
+use crate::server::rate_limit::{RateLimit, TokenBucket};
+use crate::{FromServer, ModAction};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct Group {
+    name: Arc<String>,
+    sender: broadcast::Sender<FromServer>,
+    posts: Mutex<TokenBucket>,
+    members: Mutex<Members>,
+    history: Mutex<History>,
+    /// Where posts made on this server go, to be passed on to its peers.
+    relay: broadcast::Sender<Relayed>,
+    metrics: Arc<Metrics>,
+}
+
+struct History {
+    next_sequence: u64,
+    capacity: usize,
+    /// The most recent messages, oldest first, with consecutive sequence numbers.
+    messages: VecDeque<FromServer>,
+}
+
+/// Who may do what in a group. Nicknames are given up when their users
+/// leave, and can then be claimed by anyone, so ownership and mutes are
+/// tied to connections instead.
+struct Members {
+    /// The owner's connection, or `None` if the group is empty and the
+    /// next member to join will own it.
+    owner: Option<u64>,
+    /// Banned nicknames, and the connection each was banned on if they
+    /// were a member, which may not come back under another nickname.
+    banned: HashMap<Arc<String>, Option<u64>>,
+    /// Muted connections.
+    muted: HashSet<u64>,
+    joined: HashMap<Arc<String>, Member>,
+}
+
+impl Members {
+    /// The membership of `nickname`, if it belongs to `identity`'s connection.
+    fn get(&self, nickname: &Arc<String>, identity: &Identity) -> Option<&Member> {
+        self.joined
+            .get(nickname)
+            .filter(|member| member.identity.connection == identity.connection)
+    }
+
+    /// Whether `nickname`, connected as `identity`, is banned. The owner
+    /// never is.
+    fn is_banned(&self, nickname: &Arc<String>, identity: &Identity) -> bool {
+        self.owner != Some(identity.connection)
+            && (self.banned.contains_key(nickname)
+                || self
+                    .banned
+                    .values()
+                    .any(|&banned| banned == Some(identity.connection)))
+    }
+}
+
+struct Member {
+    /// Tells this membership apart from a later one by the same user.
+    id: u64,
+    identity: Identity,
+    outbound: Arc<Outbound>,
+    kicked: watch::Sender<bool>,
+}
+
+/// What a user can't change by choosing another nickname.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Identity {
+    /// Unique to one connection, and never reused.
+    pub connection: u64,
+}
+
+impl Identity {
+    /// An identity for a new connection, which no other has had.
+    pub fn unique() -> Identity {
+        Identity {
+            connection: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Identifies one membership, so that a subscription that is winding down
+/// can't end a newer membership of the same user.
+pub struct MemberId {
+    nickname: Arc<String>,
+    id: u64,
+}
+
+impl MemberId {
+    pub fn nickname(&self) -> &Arc<String> {
+        &self.nickname
+    }
+}
+
+static NEXT_MEMBER_ID: AtomicU64 = AtomicU64::new(0);
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+impl Group {
+    /// Create a group owned by whoever joins it first, which keeps its
+    /// last `history` messages. Subscribers may fall just as far behind
+    /// before they start missing messages. Posts made here are sent to
+    /// `relay`, and counted in `metrics`.
+    pub fn new(
+        name: Arc<String>,
+        post_limit: RateLimit,
+        history: usize,
+        relay: broadcast::Sender<Relayed>,
+        metrics: Arc<Metrics>,
+    ) -> Group {
+        let (sender, _receiver) = broadcast::channel(history);
+        let posts = Mutex::new(TokenBucket::new(post_limit));
+        let members = Mutex::new(Members {
+            owner: None,
+            banned: HashMap::new(),
+            muted: HashSet::new(),
+            joined: HashMap::new(),
+        });
+
+        let history = Mutex::new(History {
+            next_sequence: 1,
+            capacity: history,
+            messages: VecDeque::with_capacity(history),
+        });
+
+        Group {
+            name,
+            sender,
+            posts,
+            members,
+            history,
+            relay,
+            metrics,
+        }
+    }
+
+    pub fn name(&self) -> &Arc<String> {
+        &self.name
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.lock().unwrap().joined.is_empty()
+    }
+
+    /// How many connections this group is forwarding messages to.
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    pub fn is_member(&self, nickname: &Arc<String>) -> bool {
+        self.members.lock().unwrap().joined.contains_key(nickname)
+    }
+
+    /// The nicknames of everyone in the group, in alphabetical order.
+    pub fn members(&self) -> Vec<Arc<String>> {
+        let mut members: Vec<_> = self
+            .members
+            .lock()
+            .unwrap()
+            .joined
+            .keys()
+            .cloned()
+            .collect();
+        members.sort();
+
+        members
+    }
+
+    /// Make `nickname`, connected as `identity`, a member and forward this
+    /// group's messages to `outbound` until it closes or the member is
+    /// kicked. A group with no owner is given to its new member.
+    ///
+    /// The receiver is created as soon as this is called, not when the
+    /// returned future is first polled, so no message posted after the call
+    /// can be missed. The future resolves to the membership that ended,
+    /// which must then be passed to `leave`.
+    pub fn join(
+        &self,
+        nickname: Arc<String>,
+        identity: Identity,
+        outbound: Arc<Outbound>,
+    ) -> Result<impl Future<Output = MemberId>, String> {
+        let mut members = self.members.lock().unwrap();
+
+        if members.is_banned(&nickname, &identity) {
+            return Err(format!("You are banned from '{}'", self.name));
+        }
+
+        if members.joined.contains_key(&nickname) {
+            return Err(format!("You are already a member of '{}'", self.name));
+        }
+
+        let id = NEXT_MEMBER_ID.fetch_add(1, Ordering::Relaxed);
+        let (kicked, kicked_receiver) = watch::channel(false);
+        let member = Member {
+            id,
+            identity,
+            outbound: outbound.clone(),
+            kicked,
+        };
+        members.joined.insert(nickname.clone(), member);
+        members.owner.get_or_insert(identity.connection);
+
+        let receiver = self.sender.subscribe();
+        let guard = outbound.track_subscription();
+        self.broadcast(FromServer::MemberJoined {
+            group_name: self.name.clone(),
+            nickname: nickname.clone(),
+        });
+
+        let subscription =
+            handle_subscriber(receiver, outbound, kicked_receiver, self.metrics.clone());
+
+        Ok(async move {
+            subscription.await;
+            drop(guard);
+            MemberId { nickname, id }
+        })
+    }
+
+    /// End a membership returned by `join`. Return false if it had
+    /// already ended.
+    ///
+    /// If the owner left, the member who has been in the group longest
+    /// takes over, and is told so.
+    pub fn leave(&self, member: &MemberId) -> bool {
+        let mut members = self.members.lock().unwrap();
+
+        let left = match members.joined.get(&member.nickname) {
+            Some(current) if current.id == member.id => {
+                members.joined.remove(&member.nickname).unwrap()
+            }
+            _ => return false,
+        };
+
+        self.broadcast(FromServer::MemberLeft {
+            group_name: self.name.clone(),
+            nickname: member.nickname.clone(),
+        });
+
+        if members.owner == Some(left.identity.connection) {
+            let successor = members
+                .joined
+                .values()
+                .min_by_key(|member| member.id)
+                .map(|successor| (successor.identity.connection, successor.outbound.clone()));
+            members.owner = successor.as_ref().map(|(connection, _)| *connection);
+
+            if let Some((_, outbound)) = successor {
+                let notice = FromServer::Moderated {
+                    group_name: self.name.clone(),
+                    action: ModAction::TransferOwnership,
+                    by: member.nickname.clone(),
+                };
+
+                // The new owner may be disconnecting too; then nobody needs telling.
+                rt::spawn(async move {
+                    let _ = outbound.send(notice).await;
+                });
+            }
+        }
+
+        true
+    }
+
+    /// Check whether `nickname`, connected as `identity`, may post to this
+    /// group right now.
+    pub fn check_post(&self, nickname: &Arc<String>, identity: &Identity) -> Result<(), String> {
+        {
+            let members = self.members.lock().unwrap();
+            if members.is_banned(nickname, identity) {
+                return Err(format!("You are banned from '{}'", self.name));
+            }
+            if members.get(nickname, identity).is_none() {
+                return Err(format!("Join '{}' to post to it", self.name));
+            }
+            if members.muted.contains(&identity.connection) {
+                return Err(format!("You are muted in '{}'", self.name));
+            }
+        }
+
+        if !self.admit_post() {
+            return Err(format!(
+                "Group '{}' is receiving too many messages; message dropped",
+                self.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the group can take another post without exceeding
+    /// its rate limit, which keeps any group from flooding its subscribers.
+    fn admit_post(&self) -> bool {
+        self.posts.lock().unwrap().try_take()
+    }
+
+    /// Post a message from someone on this server, and pass it on
+    /// to the peers.
+    pub fn post(&self, sender: Arc<String>, message: Arc<String>) {
+        self.post_relayed(sender.clone(), message.clone());
+
+        // Peers may come and go, so nobody listening is fine.
+        let _ = self.relay.send(Relayed {
+            group_name: self.name.clone(),
+            sender,
+            message,
+        });
+    }
+
+    /// Post a message that came from a peer. Peers only pass on their own
+    /// users' posts, so this one goes no further, or it would come back.
+    pub fn post_relayed(&self, sender: Arc<String>, message: Arc<String>) {
+        // Numbering, recording and sending all happen under the lock,
+        // so subscribers see the messages in sequence order.
+        let mut history = self.history.lock().unwrap();
+        let sequence = history.next_sequence;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_millis() as u64);
+
+        let packet = FromServer::Message {
+            group_name: self.name.clone(),
+            sender,
+            message,
+            sequence,
+            timestamp,
+        };
+
+        if history.messages.len() == history.capacity {
+            history.messages.pop_front();
+        }
+
+        history.messages.push_back(packet.clone());
+        history.next_sequence += 1;
+
+        self.metrics.message_posted();
+        self.broadcast(packet);
+    }
+
+    /// Return the messages numbered `from` through `to` that are still in
+    /// the history, and the sequence number of the oldest message kept.
+    pub fn fetch(
+        &self,
+        nickname: &Arc<String>,
+        from: u64,
+        to: u64,
+    ) -> Result<(Vec<FromServer>, u64), String> {
+        if !self.is_member(nickname) {
+            return Err(format!("Join '{}' to see its messages", self.name));
+        }
+
+        let history = self.history.lock().unwrap();
+        let oldest = history.next_sequence - history.messages.len() as u64;
+
+        let start = from.max(oldest);
+        let end = to.saturating_add(1).min(history.next_sequence);
+
+        let messages = (start..end)
+            .map(|sequence| history.messages[(sequence - oldest) as usize].clone())
+            .collect();
+
+        Ok((messages, oldest))
+    }
+
+    /// Tell the other members that `nickname`, connected as `identity`,
+    /// is typing.
+    pub fn typing(&self, nickname: &Arc<String>, identity: &Identity) -> Result<(), String> {
+        let members = self.members.lock().unwrap();
+
+        if members.get(nickname, identity).is_none() || members.muted.contains(&identity.connection)
+        {
+            return Err(format!("You can't post to '{}'", self.name));
+        }
+
+        self.broadcast(FromServer::Typing {
+            group_name: self.name.clone(),
+            nickname: nickname.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn broadcast(&self, packet: FromServer) {
+        // This only returns an error when there are no subscribers.
+        // A connection's outgoing side can exit, dropping its subscription,
+        // slightly before its incoming side, which may end up trying to send
+        // a message to an empty group.
+        let _ignored = self.sender.send(packet);
+    }
+
+    /// Apply `action` to `target` on behalf of `by`, who must be the owner.
+    /// Everything but banning and unbanning needs the target to be a
+    /// member, whose connection the action then applies to.
+    ///
+    /// Return the target's connection, if they are a member, so the caller
+    /// can tell them what happened.
+    pub fn moderate(
+        &self,
+        by: &Identity,
+        target: &Arc<String>,
+        action: ModAction,
+    ) -> Result<Option<Arc<Outbound>>, String> {
+        let mut members = self.members.lock().unwrap();
+
+        if members.owner != Some(by.connection) {
+            return Err(format!("Only the owner of '{}' can do that", self.name));
+        }
+
+        let identity = match members.joined.get(target) {
+            Some(member) if member.identity.connection == by.connection => {
+                return Err("You can't moderate yourself".to_string());
+            }
+            Some(member) => member.identity,
+            // Nicknames can be banned before anyone has joined with them.
+            None if action == ModAction::Ban => {
+                members.banned.insert(target.clone(), None);
+                return Ok(None);
+            }
+            None if action == ModAction::Unban => {
+                members.banned.remove(target);
+                return Ok(None);
+            }
+            None => {
+                return Err(format!("'{target}' is not a member of '{}'", self.name));
+            }
+        };
+
+        match action {
+            ModAction::Kick => {}
+            ModAction::Ban => {
+                members
+                    .banned
+                    .insert(target.clone(), Some(identity.connection));
+            }
+            ModAction::Unban => {
+                members.banned.remove(target);
+            }
+            ModAction::Mute => {
+                members.muted.insert(identity.connection);
+            }
+            ModAction::Unmute => {
+                members.muted.remove(&identity.connection);
+            }
+            ModAction::TransferOwnership => {
+                members.owner = Some(identity.connection);
+            }
+        }
+
+        let member = &members.joined[target];
+
+        if let ModAction::Kick | ModAction::Ban = action {
+            member.kicked.send_replace(true);
+        }
+
+        Ok(Some(member.outbound.clone()))
+    }
+}
+
+enum Event {
+    Received(Result<FromServer, RecvError>),
+    Closed,
+    Kicked,
+}
+
+async fn handle_subscriber(
+    mut receiver: broadcast::Receiver<FromServer>,
+    outbound: Arc<Outbound>,
+    mut kicked: watch::Receiver<bool>,
+    metrics: Arc<Metrics>,
+) {
+    loop {
+        let received = async { Event::Received(receiver.recv().await) };
+        let on_close = async {
+            outbound.closed().await;
+            Event::Closed
+        };
+        let on_kick = async {
+            let _ = kicked.wait_for(|kicked| *kicked).await;
+            Event::Kicked
+        };
+
+        let packet = match received.race(on_close).race(on_kick).await {
+            Event::Received(Ok(packet)) => packet,
+            // The client notices the gap in the sequence numbers, and can
+            // fetch whatever it missed from the group's history.
+            Event::Received(Err(RecvError::Lagged(skipped))) => {
+                metrics.lagged(skipped);
+                continue;
+            }
+            Event::Received(Err(RecvError::Closed)) | Event::Kicked => break,
+            Event::Closed => {
+                drain(&mut receiver, &outbound, &metrics).await;
+                break;
+            }
+        };
+
+        if outbound.send(packet).await.is_err() {
+            metrics.send_failed();
+            break;
+        }
+    }
+}
+
+/// Send everything `receiver` has already received, without waiting for more.
+async fn drain(
+    receiver: &mut broadcast::Receiver<FromServer>,
+    outbound: &Outbound,
+    metrics: &Metrics,
+) {
+    loop {
+        let packet = match receiver.try_recv() {
+            Ok(packet) => packet,
+            Err(TryRecvError::Lagged(skipped)) => {
+                metrics.lagged(skipped);
+                continue;
+            }
+            Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+        };
+
+        if outbound.send(packet).await.is_err() {
+            metrics.send_failed();
+            break;
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use crate::rt;
+use crate::rt::sync::broadcast;
+use crate::server::connection::Outbound;
+use crate::server::federation::Relayed;
+use crate::server::group::{Group, Identity, MemberId};
+use crate::server::metrics::Metrics;
+use crate::server::plugin::{Plugins, Reply};
+use crate::server::rate_limit::RateLimit;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub struct GroupTable {
+    groups: Mutex<HashMap<Arc<String>, Entry>>,
+    post_limit: RateLimit,
+    history: usize,
+    idle_timeout: Duration,
+    plugins: Plugins,
+    relay: broadcast::Sender<Relayed>,
+    metrics: Arc<Metrics>,
+}
+
+struct Entry {
+    group: Arc<Group>,
+    /// When the last subscriber left, if the group is empty.
+    idle_since: Option<Instant>,
+}
+
+impl GroupTable {
+    /// Create an empty table. Each group keeps its last `history` messages,
+    /// and is removed once it has had no subscribers for `idle_timeout`.
+    /// Every group is watched by `plugins`.
+    pub fn new(
+        post_limit: RateLimit,
+        history: usize,
+        idle_timeout: Duration,
+        plugins: Plugins,
+    ) -> GroupTable {
+        GroupTable {
+            groups: Mutex::new(HashMap::new()),
+            post_limit,
+            history,
+            idle_timeout,
+            plugins,
+            relay: broadcast::Sender::new(1000),
+            metrics: Arc::new(Metrics::new()),
+        }
+    }
+
+    /// The counters shared by every group and connection on this server.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Receive every post made on this server, for passing on to a peer.
+    pub fn relayed(&self) -> broadcast::Receiver<Relayed> {
+        self.relay.subscribe()
+    }
+
+    pub fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+
+    /// Every group that exists right now.
+    pub fn groups(&self) -> Vec<Arc<Group>> {
+        self.groups
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.group.clone())
+            .collect()
+    }
+
+    pub fn get(&self, name: &String) -> Option<Arc<Group>> {
+        self.groups
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|entry| entry.group.clone())
+    }
+
+    /// Make `nickname`, connected as `identity`, a member of the group called
+    /// `name`, creating the group with them as its owner if necessary. The
+    /// membership lasts until `outbound` is closed or the member is kicked.
+    pub fn join(
+        self: &Arc<Self>,
+        name: Arc<String>,
+        nickname: Arc<String>,
+        identity: Identity,
+        outbound: Arc<Outbound>,
+    ) -> Result<Arc<Group>, String> {
+        // Joining happens under the table's lock, the same one `leave` holds
+        // to remove a group, so a group can never be removed between being
+        // found here and gaining its new member.
+        let (group, subscription) = {
+            let mut groups = self.groups.lock().unwrap();
+            let entry = groups.entry(name.clone()).or_insert_with(|| Entry {
+                group: Arc::new(Group::new(
+                    name,
+                    self.post_limit,
+                    self.history,
+                    self.relay.clone(),
+                    self.metrics.clone(),
+                )),
+                idle_since: None,
+            });
+
+            let subscription = entry.group.join(nickname, identity, outbound)?;
+            entry.idle_since = None;
+
+            (entry.group.clone(), subscription)
+        };
+
+        let table = self.clone();
+        let joined = group.clone();
+
+        rt::spawn(async move {
+            let member = subscription.await;
+
+            if table.leave(&group, &member) {
+                // Whoever left can't hear private replies any more.
+                for reply in table.plugins.leave(&group, member.nickname()) {
+                    if let Reply::Group { from, message } = reply {
+                        group.post(from, Arc::new(message));
+                    }
+                }
+            }
+        });
+
+        Ok(joined)
+    }
+
+    /// End `member`'s membership in `group`, and remove the group if that
+    /// leaves it empty. Return false if the membership had already ended.
+    fn leave(self: &Arc<Self>, group: &Arc<Group>, member: &MemberId) -> bool {
+        let mut groups = self.groups.lock().unwrap();
+        let entry = match groups.get_mut(group.name()) {
+            Some(entry) if Arc::ptr_eq(&entry.group, group) => entry,
+            _ => return false,
+        };
+
+        if !group.leave(member) {
+            return false;
+        }
+
+        if !group.is_empty() {
+            return true;
+        }
+
+        if self.idle_timeout.is_zero() {
+            groups.remove(group.name());
+            return true;
+        }
+
+        let since = Instant::now();
+        entry.idle_since = Some(since);
+
+        let table = self.clone();
+        let group = group.clone();
+
+        rt::spawn(async move {
+            rt::sleep(table.idle_timeout).await;
+            table.remove_if_idle(&group, since);
+        });
+
+        true
+    }
+
+    /// Remove `group` if it has stayed empty ever since `since`.
+    /// If anyone joined in the meantime, `idle_since` will have changed.
+    fn remove_if_idle(&self, group: &Arc<Group>, since: Instant) {
+        let mut groups = self.groups.lock().unwrap();
+
+        if let Some(entry) = groups.get(group.name()) {
+            if Arc::ptr_eq(&entry.group, group) && entry.idle_since == Some(since) {
+                groups.remove(group.name());
+            }
+        }
+    }
+}
+
+/// Return an `Outbound` for a loopback connection, and the client's end,
+/// which must be kept open for as long as the `Outbound` is being used.
+#[cfg(test)]
+async fn test_outbound() -> (Arc<Outbound>, rt::net::TcpStream) {
+    use crate::codec::WireFormat;
+    use rt::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap());
+    let (client, (server, _)) = futures::future::try_join(client, listener.accept())
+        .await
+        .unwrap();
+
+    (
+        Arc::new(Outbound::new(server, WireFormat::Json, false)),
+        client,
+    )
+}
+
+#[cfg(test)]
+fn nick(nickname: &str) -> Arc<String> {
+    Arc::new(nickname.to_string())
+}
+
+#[cfg(test)]
+async fn wait_until(condition: impl Fn() -> bool) {
+    for _ in 0..100 {
+        if condition() {
+            return;
+        }
+
+        rt::sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("condition was never met");
+}
+
+#[test]
+fn test_group_disappears_after_last_subscriber_leaves() {
+    rt::block_on(async {
+        let limit = crate::server::config::Limits::default().group;
+        let table = Arc::new(GroupTable::new(limit, 1000, Duration::ZERO, Plugins::new()));
+        let name = Arc::new("Dogs".to_string());
+
+        let (first, _first_client) = test_outbound().await;
+        let (second, _second_client) = test_outbound().await;
+        table
+            .join(
+                name.clone(),
+                nick("first"),
+                Identity::unique(),
+                first.clone(),
+            )
+            .unwrap();
+        table
+            .join(
+                name.clone(),
+                nick("second"),
+                Identity::unique(),
+                second.clone(),
+            )
+            .unwrap();
+        let original = table.get(&name).unwrap();
+
+        first.close();
+        rt::sleep(Duration::from_millis(50)).await;
+        assert!(table.get(&name).is_some());
+
+        second.close();
+        wait_until(|| table.get(&name).is_none()).await;
+
+        // Joining again creates a brand new group.
+        let (third, _third_client) = test_outbound().await;
+        table
+            .join(
+                name.clone(),
+                nick("third"),
+                Identity::unique(),
+                third.clone(),
+            )
+            .unwrap();
+        let recreated = table.get(&name).unwrap();
+        assert!(!Arc::ptr_eq(&original, &recreated));
+    });
+}
+
+#[test]
+fn test_idle_group_survives_a_join_during_its_timeout() {
+    rt::block_on(async {
+        let limit = crate::server::config::Limits::default().group;
+        let timeout = Duration::from_millis(100);
+        let table = Arc::new(GroupTable::new(limit, 1000, timeout, Plugins::new()));
+        let name = Arc::new("Cats".to_string());
+
+        let (first, _first_client) = test_outbound().await;
+        table
+            .join(
+                name.clone(),
+                nick("first"),
+                Identity::unique(),
+                first.clone(),
+            )
+            .unwrap();
+        let original = table.get(&name).unwrap();
+
+        first.close();
+        rt::sleep(timeout / 2).await;
+
+        // The pending removal must not take the group away from this member.
+        let (second, _second_client) = test_outbound().await;
+        table
+            .join(
+                name.clone(),
+                nick("second"),
+                Identity::unique(),
+                second.clone(),
+            )
+            .unwrap();
+        rt::sleep(timeout * 2).await;
+        assert!(Arc::ptr_eq(&original, &table.get(&name).unwrap()));
+
+        second.close();
+        wait_until(|| table.get(&name).is_none()).await;
+    });
+}
+
+#[test]
+fn test_owner_moderates_members() {
+    use crate::ModAction;
+
+    rt::block_on(async {
+        let limit = crate::server::config::Limits::default().group;
+        let table = Arc::new(GroupTable::new(limit, 1000, Duration::ZERO, Plugins::new()));
+        let name = Arc::new("Dogs".to_string());
+
+        let (owner, _owner_client) = test_outbound().await;
+        let (member, _member_client) = test_outbound().await;
+        let (owner_id, member_id) = (Identity::unique(), Identity::unique());
+        table
+            .join(name.clone(), nick("owner"), owner_id, owner.clone())
+            .unwrap();
+        table
+            .join(name.clone(), nick("member"), member_id, member.clone())
+            .unwrap();
+        let group = table.get(&name).unwrap();
+
+        // Only the creator holds any rights.
+        assert!(group
+            .moderate(&member_id, &nick("owner"), ModAction::Kick)
+            .is_err());
+
+        group
+            .moderate(&owner_id, &nick("member"), ModAction::Mute)
+            .unwrap();
+        assert!(group.check_post(&nick("member"), &member_id).is_err());
+        assert!(group.check_post(&nick("owner"), &owner_id).is_ok());
+        assert!(group
+            .check_post(&nick("stranger"), &Identity::unique())
+            .is_err());
+
+        // A ban removes the member and keeps them out, even before they
+        // are gone. Their connection stays out under any other nickname,
+        // and the nickname stays out on any other connection.
+        group
+            .moderate(&owner_id, &nick("member"), ModAction::Ban)
+            .unwrap();
+        assert!(group
+            .check_post(&nick("member"), &member_id)
+            .is_err_and(|error| error.contains("banned")));
+        wait_until(|| !group.is_member(&nick("member"))).await;
+        assert!(table
+            .join(name.clone(), nick("disguised"), member_id, member.clone())
+            .is_err_and(|error| error.contains("banned")));
+        let reconnected = Identity::unique();
+        assert!(table
+            .join(name.clone(), nick("member"), reconnected, member.clone())
+            .is_err_and(|error| error.contains("banned")));
+
+        group
+            .moderate(&owner_id, &nick("member"), ModAction::Unban)
+            .unwrap();
+        table
+            .join(name.clone(), nick("member"), reconnected, member.clone())
+            .unwrap();
+
+        // The mute was on the old connection, not the nickname.
+        assert!(group.check_post(&nick("member"), &reconnected).is_ok());
+
+        // After handing over ownership, the old owner has no rights left.
+        group
+            .moderate(&owner_id, &nick("member"), ModAction::TransferOwnership)
+            .unwrap();
+        assert!(group
+            .moderate(&owner_id, &nick("member"), ModAction::Kick)
+            .is_err());
+        group
+            .moderate(&reconnected, &nick("owner"), ModAction::Kick)
+            .unwrap();
+    });
+}
+
+#[test]
+fn test_ownership_passes_on_when_the_owner_leaves() {
+    use crate::ModAction;
+
+    rt::block_on(async {
+        let limit = crate::server::config::Limits::default().group;
+        let table = Arc::new(GroupTable::new(limit, 1000, Duration::ZERO, Plugins::new()));
+        let name = Arc::new("Dogs".to_string());
+
+        let (owner, _owner_client) = test_outbound().await;
+        let (first, _first_client) = test_outbound().await;
+        let (second, _second_client) = test_outbound().await;
+        let (owner_id, first_id, second_id) =
+            (Identity::unique(), Identity::unique(), Identity::unique());
+        table
+            .join(name.clone(), nick("owner"), owner_id, owner.clone())
+            .unwrap();
+        table
+            .join(name.clone(), nick("first"), first_id, first.clone())
+            .unwrap();
+        table
+            .join(name.clone(), nick("second"), second_id, second.clone())
+            .unwrap();
+        let group = table.get(&name).unwrap();
+
+        owner.close();
+        wait_until(|| !group.is_member(&nick("owner"))).await;
+
+        // Whoever claims the nickname next gets none of its rights.
+        let (impostor, _impostor_client) = test_outbound().await;
+        let impostor_id = Identity::unique();
+        table
+            .join(name.clone(), nick("owner"), impostor_id, impostor.clone())
+            .unwrap();
+        assert!(group
+            .moderate(&impostor_id, &nick("second"), ModAction::Kick)
+            .is_err());
+
+        // The member who has been there longest takes over.
+        assert!(group
+            .moderate(&second_id, &nick("first"), ModAction::Kick)
+            .is_err());
+        group
+            .moderate(&first_id, &nick("owner"), ModAction::Kick)
+            .unwrap();
+    });
+}
+
+#[test]
+fn test_bans_spare_others_at_the_same_address() {
+    use crate::ModAction;
+
+    rt::block_on(async {
+        let limit = crate::server::config::Limits::default().group;
+        let table = Arc::new(GroupTable::new(limit, 1000, Duration::ZERO, Plugins::new()));
+        let name = Arc::new("Dogs".to_string());
+
+        // Like users behind one NAT, everyone here connects from 127.0.0.1.
+        let (owner, owner_client) = test_outbound().await;
+        let (pest, pest_client) = test_outbound().await;
+        let (bystander, _bystander_client) = test_outbound().await;
+        assert_eq!(
+            owner_client.local_addr().unwrap().ip(),
+            pest_client.local_addr().unwrap().ip()
+        );
+
+        let (owner_id, pest_id) = (Identity::unique(), Identity::unique());
+        table
+            .join(name.clone(), nick("owner"), owner_id, owner.clone())
+            .unwrap();
+        table
+            .join(name.clone(), nick("pest"), pest_id, pest.clone())
+            .unwrap();
+        let group = table.get(&name).unwrap();
+
+        group
+            .moderate(&owner_id, &nick("pest"), ModAction::Ban)
+            .unwrap();
+        wait_until(|| !group.is_member(&nick("pest"))).await;
+
+        // Only the pest is kept out.
+        assert!(group.check_post(&nick("owner"), &owner_id).is_ok());
+        let bystander_id = Identity::unique();
+        table
+            .join(
+                name.clone(),
+                nick("bystander"),
+                bystander_id,
+                bystander.clone(),
+            )
+            .unwrap();
+        assert!(group.check_post(&nick("bystander"), &bystander_id).is_ok());
+
+        // The owner can't ban themselves, either.
+        assert!(group
+            .moderate(&owner_id, &nick("owner"), ModAction::Ban)
+            .is_err());
+        assert!(group.check_post(&nick("owner"), &owner_id).is_ok());
+    });
+}
@@ -0,0 +1,235 @@
+//! Counters describing what the server is doing, for the admin listener.
+
+use crate::server::group_table::GroupTable;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// How many seconds `messages_per_second` is averaged over.
+const RATE_WINDOW: usize = 10;
+
+pub struct Metrics {
+    started: Instant,
+    connections: AtomicU64,
+    connections_total: AtomicU64,
+    messages_total: AtomicU64,
+    /// Messages posted in each of the last few seconds.
+    recent: Mutex<[Second; RATE_WINDOW]>,
+    lag_events: AtomicU64,
+    dropped_sends: AtomicU64,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Second {
+    /// Seconds since `started`.
+    second: u64,
+    messages: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            started: Instant::now(),
+            connections: AtomicU64::new(0),
+            connections_total: AtomicU64::new(0),
+            messages_total: AtomicU64::new(0),
+            recent: Mutex::new([Second::default(); RATE_WINDOW]),
+            lag_events: AtomicU64::new(0),
+            dropped_sends: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a client connection as open until the returned guard is dropped.
+    pub fn track_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+
+        ConnectionGuard(self.clone())
+    }
+
+    pub fn message_posted(&self) {
+        self.messages_total.fetch_add(1, Ordering::Relaxed);
+
+        let now = self.started.elapsed().as_secs();
+        let mut recent = self.recent.lock().unwrap();
+        let slot = &mut recent[now as usize % RATE_WINDOW];
+
+        if slot.second != now {
+            *slot = Second {
+                second: now,
+                messages: 0,
+            };
+        }
+
+        slot.messages += 1;
+    }
+
+    /// Note that a subscriber fell behind and missed `skipped` messages.
+    pub fn lagged(&self, skipped: u64) {
+        self.lag_events.fetch_add(1, Ordering::Relaxed);
+        self.dropped_sends.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    /// Note that a packet could not be sent to a client.
+    pub fn send_failed(&self) {
+        self.dropped_sends.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The average rate over the last `RATE_WINDOW` complete seconds.
+    fn messages_per_second(&self) -> f64 {
+        let now = self.started.elapsed().as_secs();
+        let window = now.saturating_sub(RATE_WINDOW as u64)..now;
+        let recent = self.recent.lock().unwrap();
+
+        let messages: u64 = recent
+            .iter()
+            .filter(|slot| window.contains(&slot.second))
+            .map(|slot| slot.messages)
+            .sum();
+
+        messages as f64 / RATE_WINDOW as f64
+    }
+
+    /// Take a reading of every counter, along with the groups in `groups`.
+    pub fn snapshot(&self, groups: &GroupTable) -> Snapshot {
+        let subscribers: BTreeMap<_, _> = groups
+            .groups()
+            .iter()
+            .map(|group| (group.name().clone(), group.subscribers()))
+            .collect();
+
+        Snapshot {
+            connections: self.connections.load(Ordering::Relaxed),
+            connections_total: self.connections_total.load(Ordering::Relaxed),
+            groups: subscribers.len(),
+            subscribers,
+            messages_total: self.messages_total.load(Ordering::Relaxed),
+            messages_per_second: self.messages_per_second(),
+            lag_events: self.lag_events.load(Ordering::Relaxed),
+            dropped_sends: self.dropped_sends.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct ConnectionGuard(Arc<Metrics>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The counters at one moment, as served to monitoring tools.
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    /// Client connections open right now.
+    pub connections: u64,
+    /// Client connections accepted since the server started.
+    pub connections_total: u64,
+    pub groups: usize,
+    /// How many connections each group is forwarding messages to.
+    pub subscribers: BTreeMap<Arc<String>, usize>,
+    pub messages_total: u64,
+    pub messages_per_second: f64,
+    /// How many times a subscriber fell so far behind that it missed messages.
+    pub lag_events: u64,
+    /// Packets that never reached their client, through lag or a failed send.
+    pub dropped_sends: u64,
+}
+
+impl Snapshot {
+    /// Format the snapshot in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        let mut gauge = |name: &str, help: &str, value: &dyn Display| {
+            header(&mut text, name, "gauge", help);
+            let _ = writeln!(text, "chat_{name} {value}");
+        };
+
+        gauge(
+            "connections",
+            "Client connections open right now.",
+            &self.connections,
+        );
+        gauge("groups", "Groups that exist right now.", &self.groups);
+        gauge(
+            "messages_per_second",
+            "Messages posted per second, averaged over the last ten seconds.",
+            &self.messages_per_second,
+        );
+
+        let mut counter = |name: &str, help: &str, value: u64| {
+            header(&mut text, name, "counter", help);
+            let _ = writeln!(text, "chat_{name} {value}");
+        };
+
+        counter(
+            "connections_total",
+            "Client connections accepted.",
+            self.connections_total,
+        );
+        counter(
+            "messages_total",
+            "Messages posted to groups.",
+            self.messages_total,
+        );
+        counter(
+            "lag_events_total",
+            "Times a subscriber fell behind and missed messages.",
+            self.lag_events,
+        );
+        counter(
+            "dropped_sends_total",
+            "Packets that never reached their client.",
+            self.dropped_sends,
+        );
+
+        let help = "Connections subscribed to each group.";
+        header(&mut text, "group_subscribers", "gauge", help);
+        for (group, subscribers) in &self.subscribers {
+            let group = escape_label(group);
+            let _ = writeln!(
+                text,
+                "chat_group_subscribers{{group=\"{group}\"}} {subscribers}"
+            );
+        }
+
+        text
+    }
+}
+
+fn header(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP chat_{name} {help}");
+    let _ = writeln!(text, "# TYPE chat_{name} {kind}");
+}
+
+/// Escape a label value, which appears between double quotes.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[test]
+fn test_prometheus_format() {
+    let snapshot = Snapshot {
+        connections: 2,
+        connections_total: 5,
+        groups: 1,
+        subscribers: BTreeMap::from([(Arc::new("say \"hi\"".to_string()), 2)]),
+        messages_total: 30,
+        messages_per_second: 1.5,
+        lag_events: 1,
+        dropped_sends: 7,
+    };
+
+    let text = snapshot.to_prometheus();
+    assert!(text.contains("# TYPE chat_connections gauge\nchat_connections 2\n"));
+    assert!(text.contains("chat_group_subscribers{group=\"say \\\"hi\\\"\"} 2\n"));
+    assert!(text.contains("chat_messages_per_second 1.5\n"));
+    assert!(text.contains("chat_dropped_sends_total 7\n"));
+}
@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+const GUEST_PREFIX: &str = "guest-";
+const MAX_NICKNAME_LEN: usize = 32;
+
+/// The nicknames of everyone currently connected.
+/// No two connections may go by the same name at the same time.
+pub struct Nicknames {
+    in_use: Mutex<HashSet<Arc<String>>>,
+    next_guest: AtomicU64,
+}
+
+impl Nicknames {
+    pub fn new() -> Nicknames {
+        Nicknames {
+            in_use: Mutex::new(HashSet::new()),
+            next_guest: AtomicU64::new(1),
+        }
+    }
+
+    /// Hand out a fresh `guest-N` name. Users can't choose names with the
+    /// guest prefix, so these never collide.
+    pub fn guest(&self) -> Arc<String> {
+        let number = self.next_guest.fetch_add(1, Ordering::Relaxed);
+        let nickname = Arc::new(format!("{GUEST_PREFIX}{number}"));
+
+        self.in_use.lock().unwrap().insert(nickname.clone());
+
+        nickname
+    }
+
+    /// Reserve `nickname` if it is valid and nobody else is using it.
+    pub fn claim(&self, nickname: &Arc<String>) -> Result<(), String> {
+        if nickname.is_empty()
+            || nickname.len() > MAX_NICKNAME_LEN
+            || nickname.contains(char::is_whitespace)
+        {
+            return Err(format!(
+                "Nicknames must be 1 to {MAX_NICKNAME_LEN} bytes without spaces"
+            ));
+        }
+
+        // Users on other servers show up as `nickname@server`.
+        if nickname.contains('@') {
+            return Err("Nicknames may not contain '@'".to_string());
+        }
+
+        if nickname.starts_with(GUEST_PREFIX) {
+            return Err(format!("Nicknames may not start with '{GUEST_PREFIX}'"));
+        }
+
+        if !self.in_use.lock().unwrap().insert(nickname.clone()) {
+            return Err(format!("The nickname '{nickname}' is already taken"));
+        }
+
+        Ok(())
+    }
+
+    /// Keep `nickname` from ever being claimed, without checking it.
+    pub fn reserve(&self, nickname: &str) {
+        self.in_use
+            .lock()
+            .unwrap()
+            .insert(Arc::new(nickname.to_string()));
+    }
+
+    pub fn release(&self, nickname: &Arc<String>) {
+        self.in_use.lock().unwrap().remove(nickname);
+    }
+}
@@ -0,0 +1,185 @@
+//! Hooks for adding bots and commands to the server without touching
+//! the code that serves connections.
+
+use crate::server::group::Group;
+use std::sync::Arc;
+
+mod roll;
+mod welcome;
+mod who;
+
+pub use roll::Roll;
+pub use welcome::Welcome;
+pub use who::Who;
+
+/// Something that watches the groups and takes part in them.
+///
+/// Every method has a default that does nothing, so a plugin only
+/// implements the events it cares about. They are called while a
+/// connection waits, so they should return quickly.
+pub trait Plugin: Send + Sync {
+    /// The name the plugin's replies are sent under. Nobody can take it
+    /// as a nickname.
+    fn name(&self) -> &str;
+
+    /// Look at a post before it reaches the group. The plugin may rewrite
+    /// `message`, or drop the post so that neither the group nor any later
+    /// plugin sees it.
+    fn on_post(&self, _context: &mut Context, _message: &mut Arc<String>) -> Verdict {
+        Verdict::Deliver
+    }
+
+    /// Someone has just joined the group.
+    fn on_join(&self, _context: &mut Context) {}
+
+    /// Someone has left the group. They are already gone, so anything
+    /// sent to them privately is dropped.
+    fn on_leave(&self, _context: &mut Context) {}
+}
+
+/// What should happen to a post after a plugin has seen it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Deliver,
+    Drop,
+}
+
+/// What a plugin knows about the event it is handling, and the way
+/// it answers.
+pub struct Context<'a> {
+    group: &'a Group,
+    nickname: &'a Arc<String>,
+    from: Arc<String>,
+    replies: Vec<Reply>,
+}
+
+pub(crate) enum Reply {
+    /// Sent to the user who caused the event, from the plugin `from`.
+    Private { from: Arc<String>, message: String },
+    /// Posted to the group by the plugin `from`.
+    Group { from: Arc<String>, message: String },
+}
+
+impl<'a> Context<'a> {
+    fn new(
+        group: &'a Group,
+        nickname: &'a Arc<String>,
+        plugin: &dyn Plugin,
+        replies: Vec<Reply>,
+    ) -> Context<'a> {
+        Context {
+            group,
+            nickname,
+            from: Arc::new(plugin.name().to_string()),
+            replies,
+        }
+    }
+
+    pub fn group_name(&self) -> &Arc<String> {
+        self.group.name()
+    }
+
+    /// The user who posted, joined or left.
+    pub fn nickname(&self) -> &Arc<String> {
+        self.nickname
+    }
+
+    /// The group's current members, in alphabetical order.
+    pub fn members(&self) -> Vec<Arc<String>> {
+        self.group.members()
+    }
+
+    /// Send `message` to the user who caused the event, and no one else.
+    pub fn reply_privately(&mut self, message: impl Into<String>) {
+        self.replies.push(Reply::Private {
+            from: self.from.clone(),
+            message: message.into(),
+        });
+    }
+
+    /// Post `message` to the whole group. It is posted after the event
+    /// that caused it, so a command is seen before its answer.
+    pub fn reply_to_group(&mut self, message: impl Into<String>) {
+        self.replies.push(Reply::Group {
+            from: self.from.clone(),
+            message: message.into(),
+        });
+    }
+}
+
+/// The plugins a server runs, in the order they see each event.
+#[derive(Default)]
+pub struct Plugins(Vec<Box<dyn Plugin>>);
+
+impl Plugins {
+    pub fn new() -> Plugins {
+        Plugins::default()
+    }
+
+    /// The plugins that come with the server: `/roll`, `/who` and a welcome
+    /// message for everyone who joins.
+    pub fn builtin() -> Plugins {
+        let mut plugins = Plugins::new();
+        plugins.register(Roll::new());
+        plugins.register(Who);
+        plugins.register(Welcome);
+
+        plugins
+    }
+
+    pub fn register(&mut self, plugin: impl Plugin + 'static) {
+        self.0.push(Box::new(plugin));
+    }
+
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|plugin| plugin.name())
+    }
+
+    /// Pass a post by `nickname` through every plugin. Return the message
+    /// to deliver, if it survived, and the plugins' replies.
+    pub(crate) fn post(
+        &self,
+        group: &Group,
+        nickname: &Arc<String>,
+        mut message: Arc<String>,
+    ) -> (Option<Arc<String>>, Vec<Reply>) {
+        let mut replies = Vec::new();
+
+        for plugin in &self.0 {
+            let mut context = Context::new(group, nickname, plugin.as_ref(), replies);
+            let verdict = plugin.on_post(&mut context, &mut message);
+            replies = context.replies;
+
+            if verdict == Verdict::Drop {
+                return (None, replies);
+            }
+        }
+
+        (Some(message), replies)
+    }
+
+    pub(crate) fn join(&self, group: &Group, nickname: &Arc<String>) -> Vec<Reply> {
+        self.each(group, nickname, |plugin, context| plugin.on_join(context))
+    }
+
+    pub(crate) fn leave(&self, group: &Group, nickname: &Arc<String>) -> Vec<Reply> {
+        self.each(group, nickname, |plugin, context| plugin.on_leave(context))
+    }
+
+    fn each(
+        &self,
+        group: &Group,
+        nickname: &Arc<String>,
+        event: impl Fn(&dyn Plugin, &mut Context),
+    ) -> Vec<Reply> {
+        let mut replies = Vec::new();
+
+        for plugin in &self.0 {
+            let mut context = Context::new(group, nickname, plugin.as_ref(), replies);
+            event(plugin.as_ref(), &mut context);
+            replies = context.replies;
+        }
+
+        replies
+    }
+}
@@ -0,0 +1,124 @@
+use super::{Context, Plugin, Verdict};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+
+const MAX_DICE: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+
+/// Rolls dice for the whole group to see: `/roll 2d6`, or just `/roll`
+/// for a single six-sided die.
+pub struct Roll {
+    /// The state of a xorshift generator. Dice don't need anything better.
+    state: Mutex<u64>,
+}
+
+impl Roll {
+    pub fn new() -> Roll {
+        // `RandomState` is seeded randomly, which makes it a handy source
+        // of a seed without another dependency.
+        let seed = RandomState::new().build_hasher().finish();
+
+        Roll {
+            state: Mutex::new(seed | 1),
+        }
+    }
+
+    /// Return a number from 1 to `sides`.
+    fn die(&self, sides: u32) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+
+        (*state % sides as u64) as u32 + 1
+    }
+}
+
+impl Default for Roll {
+    fn default() -> Roll {
+        Roll::new()
+    }
+}
+
+impl Plugin for Roll {
+    fn name(&self) -> &str {
+        "roll"
+    }
+
+    fn on_post(&self, context: &mut Context, message: &mut Arc<String>) -> Verdict {
+        let spec = match message.trim().strip_prefix("/roll") {
+            Some(spec) if spec.is_empty() || spec.starts_with(' ') => spec.trim(),
+            _ => return Verdict::Deliver,
+        };
+
+        let (dice, sides) = match parse_dice(spec) {
+            Some(dice) => dice,
+            None => {
+                context.reply_privately(format!(
+                    "Usage: /roll NdM, with up to {MAX_DICE} dice of 2 to {MAX_SIDES} sides"
+                ));
+                return Verdict::Drop;
+            }
+        };
+
+        let rolls: Vec<u32> = (0..dice).map(|_| self.die(sides)).collect();
+        let total: u32 = rolls.iter().sum();
+        let shown = rolls
+            .iter()
+            .map(|roll| roll.to_string())
+            .collect::<Vec<_>>()
+            .join(" + ");
+
+        let result = if dice == 1 {
+            format!("{} rolled {dice}d{sides}: {total}", context.nickname())
+        } else {
+            format!(
+                "{} rolled {dice}d{sides}: {shown} = {total}",
+                context.nickname()
+            )
+        };
+        context.reply_to_group(result);
+
+        Verdict::Deliver
+    }
+}
+
+/// Parse dice written as `NdM`, where `N` may be left out. An empty
+/// `spec` means one six-sided die.
+fn parse_dice(spec: &str) -> Option<(u32, u32)> {
+    if spec.is_empty() {
+        return Some((1, 6));
+    }
+
+    let (dice, sides) = spec.split_once(['d', 'D'])?;
+    let dice = if dice.is_empty() {
+        1
+    } else {
+        dice.parse().ok()?
+    };
+    let sides = sides.parse().ok()?;
+
+    if !(1..=MAX_DICE).contains(&dice) || !(2..=MAX_SIDES).contains(&sides) {
+        return None;
+    }
+
+    Some((dice, sides))
+}
+
+#[test]
+fn test_parse_dice() {
+    assert_eq!(parse_dice(""), Some((1, 6)));
+    assert_eq!(parse_dice("2d6"), Some((2, 6)));
+    assert_eq!(parse_dice("d20"), Some((1, 20)));
+    assert_eq!(parse_dice("3D8"), Some((3, 8)));
+    assert_eq!(parse_dice("0d6"), None);
+    assert_eq!(parse_dice("2d1"), None);
+    assert_eq!(parse_dice("1000d6"), None);
+    assert_eq!(parse_dice("two dice"), None);
+
+    let roll = Roll::new();
+    assert!((0..1000)
+        .map(|_| roll.die(6))
+        .all(|die| (1..=6).contains(&die)));
+}
@@ -0,0 +1,26 @@
+use super::{Context, Plugin};
+
+/// Greets everyone who joins a group.
+pub struct Welcome;
+
+impl Plugin for Welcome {
+    fn name(&self) -> &str {
+        "welcome"
+    }
+
+    fn on_join(&self, context: &mut Context) {
+        // The new member may already have been kicked again.
+        let others = context.members().len().saturating_sub(1);
+        let company = match others {
+            0 => "You're the first one here.".to_string(),
+            1 => "There is 1 other member here.".to_string(),
+            others => format!("There are {others} other members here."),
+        };
+
+        context.reply_privately(format!(
+            "Welcome to {}, {}! {company} Say /who to see them, or /roll to roll some dice.",
+            context.group_name(),
+            context.nickname()
+        ));
+    }
+}
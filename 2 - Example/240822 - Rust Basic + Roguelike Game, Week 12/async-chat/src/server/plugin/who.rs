@@ -0,0 +1,28 @@
+use super::{Context, Plugin, Verdict};
+use std::sync::Arc;
+
+/// Answers `/who` with the group's members, for the asker's eyes only.
+pub struct Who;
+
+impl Plugin for Who {
+    fn name(&self) -> &str {
+        "who"
+    }
+
+    fn on_post(&self, context: &mut Context, message: &mut Arc<String>) -> Verdict {
+        if message.trim() != "/who" {
+            return Verdict::Deliver;
+        }
+
+        let members = context
+            .members()
+            .iter()
+            .map(|member| member.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        context.reply_privately(format!("Members of {}: {members}", context.group_name()));
+
+        Verdict::Drop
+    }
+}
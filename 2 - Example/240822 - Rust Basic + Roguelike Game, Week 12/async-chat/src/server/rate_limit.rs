@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+/// How fast something may go: `per_second` on average, with bursts of up to
+/// `burst` at once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+/// The classic token bucket: it holds up to `burst` tokens, refills at
+/// `per_second`, and every event has to take a token to go through.
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full bucket.
+    pub fn new(limit: RateLimit) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token if one is available right now.
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        let refill = elapsed.as_secs_f64() * self.limit.per_second;
+
+        self.tokens = (self.tokens + refill).min(self.limit.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Counts how often a connection exceeds its limit.
+///
+/// This is itself a token bucket: every violation takes a strike, and one
+/// strike is forgiven every `forgive_after`, so only clients that keep
+/// flooding run out.
+pub struct Strikes(TokenBucket);
+
+impl Strikes {
+    pub fn new(max_strikes: u32, forgive_after: Duration) -> Strikes {
+        Strikes(TokenBucket::new(RateLimit {
+            per_second: 1.0 / forgive_after.as_secs_f64(),
+            burst: max_strikes,
+        }))
+    }
+
+    /// Record a violation. Return `false` once the client has used up
+    /// all of its strikes.
+    pub fn strike(&mut self) -> bool {
+        self.0.try_take()
+    }
+}
+
+#[test]
+fn test_bucket_allows_burst_then_refills() {
+    let start = Instant::now();
+    let mut bucket = TokenBucket::new(RateLimit {
+        per_second: 2.0,
+        burst: 3,
+    });
+    bucket.last_refill = start;
+
+    assert!(bucket.try_take_at(start));
+    assert!(bucket.try_take_at(start));
+    assert!(bucket.try_take_at(start));
+    assert!(!bucket.try_take_at(start));
+
+    // Half a second at two tokens per second buys exactly one more post.
+    let later = start + Duration::from_millis(500);
+    assert!(bucket.try_take_at(later));
+    assert!(!bucket.try_take_at(later));
+
+    // A long pause never refills past the burst size.
+    let much_later = later + Duration::from_secs(60);
+    for _ in 0..3 {
+        assert!(bucket.try_take_at(much_later));
+    }
+    assert!(!bucket.try_take_at(much_later));
+}
@@ -0,0 +1,54 @@
+use crate::rt::sync::watch;
+
+/// Lets every part of the server find out that it should wind down,
+/// and lets `main` wait until every connection has done so.
+pub struct Shutdown {
+    requested: watch::Sender<bool>,
+    connections: watch::Sender<usize>,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Shutdown {
+        Shutdown {
+            requested: watch::Sender::new(false),
+            connections: watch::Sender::new(0),
+        }
+    }
+
+    /// Ask the server to shut down. This is safe to call from a signal
+    /// handler's thread.
+    pub fn request(&self) {
+        self.requested.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Wait until `request` has been called.
+    pub async fn requested(&self) {
+        let mut requested = self.requested.subscribe();
+        let _ = requested.wait_for(|requested| *requested).await;
+    }
+
+    /// Count a connection as active until the returned guard is dropped.
+    pub fn track_connection(&self) -> ConnectionGuard {
+        self.connections.send_modify(|count| *count += 1);
+
+        ConnectionGuard(self.connections.clone())
+    }
+
+    /// Wait until every tracked connection has finished.
+    pub async fn connections_finished(&self) {
+        let mut connections = self.connections.subscribe();
+        let _ = connections.wait_for(|count| *count == 0).await;
+    }
+}
+
+pub struct ConnectionGuard(watch::Sender<usize>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
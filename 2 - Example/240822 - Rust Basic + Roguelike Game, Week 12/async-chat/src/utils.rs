@@ -1,4 +1,5 @@
-use async_std::prelude::*;
+use crate::codec::Codec;
+use futures::prelude::*;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::error::Error;
@@ -9,7 +10,7 @@ pub type ChatResult<T> = Result<T, ChatError>;
 
 pub async fn send_as_json<S, P>(outbound: &mut S, packet: &P) -> ChatResult<()>
 where
-    S: async_std::io::Write + Unpin,
+    S: AsyncWrite + Unpin,
     P: Serialize,
 {
     let mut json = serde_json::to_string(&packet)?;
@@ -22,7 +23,7 @@ where
 
 pub fn receive_as_json<S, P>(inbound: S) -> impl Stream<Item = ChatResult<P>>
 where
-    S: async_std::io::BufRead + Unpin,
+    S: AsyncBufRead + Unpin,
     P: DeserializeOwned,
 {
     inbound.lines().map(|line_result| -> ChatResult<P> {
@@ -32,3 +33,42 @@ where
         Ok(parsed)
     })
 }
+
+pub async fn send_packet<S, C, P>(outbound: &mut S, codec: &C, packet: &P) -> ChatResult<()>
+where
+    S: AsyncWrite + Unpin,
+    C: Codec,
+    P: Serialize,
+{
+    let frame = codec.encode(packet)?;
+
+    outbound.write_all(&frame).await?;
+
+    Ok(())
+}
+
+/// Read one packet from `inbound` using `codec`.
+/// Return `None` if the stream ended cleanly.
+pub async fn receive_packet<S, C, P>(inbound: &mut S, codec: &C) -> ChatResult<Option<P>>
+where
+    S: AsyncBufRead + Unpin + Send,
+    C: Codec,
+    P: DeserializeOwned,
+{
+    match codec.read_frame(inbound).await? {
+        Some(frame) => Ok(Some(codec.decode(&frame)?)),
+        None => Ok(None),
+    }
+}
+
+/// Format a timestamp in milliseconds since the Unix epoch as a UTC time of day.
+pub fn format_time(timestamp: u64) -> String {
+    let seconds = timestamp / 1000 % (24 * 60 * 60);
+
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
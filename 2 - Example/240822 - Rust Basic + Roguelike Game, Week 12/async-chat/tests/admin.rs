@@ -0,0 +1,106 @@
+mod common;
+
+use async_chat::codec::WireFormat;
+use async_chat::rt;
+use async_chat::rt::net::{SocketAddr, TcpStream};
+use async_chat::server::rate_limit::RateLimit;
+use async_chat::FromServer;
+use common::{start_server, TestClient};
+use futures::prelude::*;
+
+/// Make one HTTP request, and return the status line and the body.
+async fn get(address: SocketAddr, path: &str) -> (String, String) {
+    let mut socket = TcpStream::connect(address).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    socket.write_all(request.as_bytes()).await.unwrap();
+
+    // The server closes the connection once it has answered.
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap().to_string();
+
+    (status, body.to_string())
+}
+
+#[test]
+fn test_metrics_report_connections_groups_and_messages() {
+    rt::block_on(async {
+        let server = start_server(|config| {
+            config.admin_address = Some("127.0.0.1:0".to_string());
+        })
+        .await;
+        let admin = server.admin_address.unwrap();
+
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Binary).await;
+        alice.join("Dogs").await;
+        bob.join("Dogs").await;
+
+        for message in ["one", "two", "three"] {
+            alice.post("Dogs", message).await;
+        }
+        alice.sync().await;
+
+        let (status, body) = get(admin, "/metrics.json").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+
+        let metrics: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(metrics["connections"], 2);
+        assert_eq!(metrics["groups"], 1);
+        assert_eq!(metrics["subscribers"]["Dogs"], 2);
+        assert_eq!(metrics["messages_total"], 3);
+        assert_eq!(metrics["lag_events"], 0);
+
+        let (status, body) = get(admin, "/metrics").await;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("\nchat_connections 2\n"));
+        assert!(body.contains("\nchat_group_subscribers{group=\"Dogs\"} 2\n"));
+        assert!(body.contains("\nchat_messages_total 3\n"));
+
+        let (status, _) = get(admin, "/nowhere").await;
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+    });
+}
+
+#[test]
+fn test_metrics_count_lagging_subscribers() {
+    rt::block_on(async {
+        let server = start_server(|config| {
+            let unlimited = RateLimit {
+                per_second: 1e6,
+                burst: 1_000_000,
+            };
+            config.admin_address = Some("127.0.0.1:0".to_string());
+            config.limits.connection = unlimited;
+            config.limits.group = unlimited;
+            config.history = 16;
+        })
+        .await;
+
+        // Far more than the socket buffers hold, so while the reader isn't
+        // reading, its subscription falls behind and misses messages.
+        let mut reader = TestClient::connect(server.address, "reader", WireFormat::Binary).await;
+        let mut writer = TestClient::connect(server.address, "writer", WireFormat::Binary).await;
+        reader.join("Dogs").await;
+        writer.join("Dogs").await;
+
+        let big = "x".repeat(64 * 1024);
+        for _ in 0..400 {
+            writer.post("Dogs", &big).await;
+        }
+        writer.sync().await;
+
+        // The subscription only notices it lagged once it catches up.
+        while !matches!(
+            reader.receive_reply().await,
+            FromServer::Message { sequence: 400, .. }
+        ) {}
+
+        let (_, body) = get(server.admin_address.unwrap(), "/metrics.json").await;
+        let metrics: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(metrics["lag_events"].as_u64().unwrap() > 0);
+        assert!(metrics["dropped_sends"].as_u64().unwrap() > 0);
+    });
+}
@@ -0,0 +1,221 @@
+//! Helpers for running a real server in-process and talking to it
+//! the way a client would.
+
+// Each test file compiles its own copy of this module and uses only part of it.
+#![allow(dead_code)]
+
+use async_chat::codec::{JsonCodec, WireFormat};
+use async_chat::rt;
+use async_chat::rt::net::{SocketAddr, TcpStream};
+use async_chat::server::config::Config;
+use async_chat::server::plugin::Plugins;
+use async_chat::server::shutdown::Shutdown;
+use async_chat::server::Server;
+use async_chat::utils::{self, ChatResult};
+use async_chat::{FromClient, FromServer};
+use futures::io::BufReader;
+use futures::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long to wait for a reply before deciding it is never coming.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A server running on an ephemeral localhost port.
+pub struct TestServer {
+    pub address: SocketAddr,
+    pub websocket_address: Option<SocketAddr>,
+    pub federation_address: Option<SocketAddr>,
+    pub admin_address: Option<SocketAddr>,
+    pub shutdown: Arc<Shutdown>,
+    pub task: rt::JoinHandle<ChatResult<()>>,
+}
+
+/// Start a server with `configure` applied to the default settings.
+pub async fn start_server(configure: impl FnOnce(&mut Config)) -> TestServer {
+    start_server_with_plugins(Plugins::new(), configure).await
+}
+
+pub async fn start_server_with_plugins(
+    plugins: Plugins,
+    configure: impl FnOnce(&mut Config),
+) -> TestServer {
+    let mut config = Config::new("127.0.0.1:0");
+    configure(&mut config);
+
+    let server = Server::bind(config, plugins).await.unwrap();
+    let address = server.local_addr().unwrap();
+    let websocket_address = server.websocket_addr().unwrap();
+    let federation_address = server.federation_addr().unwrap();
+    let admin_address = server.admin_addr().unwrap();
+    let shutdown = server.shutdown();
+    let task = rt::spawn(server.run());
+
+    TestServer {
+        address,
+        websocket_address,
+        federation_address,
+        admin_address,
+        shutdown,
+        task,
+    }
+}
+
+pub fn arc(text: &str) -> Arc<String> {
+    Arc::new(text.to_string())
+}
+
+/// One connection to the server, driven step by step by a test.
+pub struct TestClient {
+    pub nickname: Arc<String>,
+    to_server: TcpStream,
+    from_server: BufReader<TcpStream>,
+    wire_format: WireFormat,
+    /// Packets that arrived while waiting for something else.
+    pending: VecDeque<FromServer>,
+}
+
+impl TestClient {
+    /// Connect, negotiate `wire_format` and take the name `nickname`.
+    pub async fn connect(address: SocketAddr, nickname: &str, wire_format: WireFormat) -> Self {
+        let mut to_server = TcpStream::connect(address).await.unwrap();
+        let mut from_server = BufReader::new(to_server.clone());
+
+        let hello = FromClient::Hello { wire_format };
+        utils::send_packet(&mut to_server, &JsonCodec, &hello)
+            .await
+            .unwrap();
+        let welcome = utils::receive_packet(&mut from_server, &JsonCodec)
+            .await
+            .unwrap();
+        assert_eq!(welcome, Some(FromServer::Welcome { wire_format }));
+
+        let mut client = TestClient {
+            nickname: arc(nickname),
+            to_server,
+            from_server,
+            wire_format,
+            pending: VecDeque::new(),
+        };
+
+        client
+            .send(FromClient::SetNickname {
+                nickname: client.nickname.clone(),
+            })
+            .await;
+
+        client
+    }
+
+    /// Connect the way clients from before the `Hello` handshake did: JSON
+    /// lines from the first packet on, under whatever guest name the
+    /// server hands out. `nickname` is left empty, since we're never told it.
+    pub async fn connect_legacy(address: SocketAddr) -> Self {
+        let to_server = TcpStream::connect(address).await.unwrap();
+        let from_server = BufReader::new(to_server.clone());
+
+        TestClient {
+            nickname: arc(""),
+            to_server,
+            from_server,
+            wire_format: WireFormat::Json,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub async fn send(&mut self, request: FromClient) {
+        utils::send_packet(&mut self.to_server, &self.wire_format, &request)
+            .await
+            .unwrap();
+        self.to_server.flush().await.unwrap();
+    }
+
+    /// Join `group_name`, and wait until the server says we're in.
+    pub async fn join(&mut self, group_name: &str) {
+        self.send(FromClient::Join {
+            group_name: arc(group_name),
+        })
+        .await;
+
+        let joined = FromServer::MemberJoined {
+            group_name: arc(group_name),
+            nickname: self.nickname.clone(),
+        };
+
+        loop {
+            let packet = self.read().await.expect("the server closed the connection");
+
+            if packet == joined {
+                return;
+            }
+
+            // Keep anything that overtook the announcement, like a plugin's greeting.
+            self.pending.push_back(packet);
+        }
+    }
+
+    pub async fn post(&mut self, group_name: &str, message: &str) {
+        self.send(FromClient::Post {
+            group_name: arc(group_name),
+            message: arc(message),
+        })
+        .await;
+    }
+
+    /// Return the next packet from the server, or `None` if it closed
+    /// the connection. Panic if nothing arrives in time.
+    pub async fn try_receive(&mut self) -> Option<FromServer> {
+        match self.pending.pop_front() {
+            Some(packet) => Some(packet),
+            None => self.read().await,
+        }
+    }
+
+    async fn read(&mut self) -> Option<FromServer> {
+        let received = utils::receive_packet(&mut self.from_server, &self.wire_format);
+
+        rt::timeout(REPLY_TIMEOUT, received)
+            .await
+            .expect("timed out waiting for the server")
+            .unwrap()
+    }
+
+    pub async fn receive(&mut self) -> FromServer {
+        self.try_receive()
+            .await
+            .expect("the server closed the connection")
+    }
+
+    /// Return the next packet that isn't news about other members.
+    pub async fn receive_reply(&mut self) -> FromServer {
+        loop {
+            match self.receive().await {
+                FromServer::MemberJoined { .. }
+                | FromServer::MemberLeft { .. }
+                | FromServer::Typing { .. } => continue,
+                reply => return reply,
+            }
+        }
+    }
+
+    /// Send a request that always fails, and wait for its error. The server
+    /// handles each connection's requests in order, so once this returns,
+    /// everything sent before it has been dealt with. Anything else that
+    /// arrives in the meantime is kept for the next `receive`.
+    pub async fn sync(&mut self) {
+        self.post("no such group", "ping").await;
+
+        loop {
+            match self.read().await.expect("the server closed the connection") {
+                FromServer::Error(message) if message.contains("no such group") => return,
+                packet => self.pending.push_back(packet),
+            }
+        }
+    }
+
+    /// The packets that have arrived but not been received yet.
+    pub fn pending(&self) -> impl Iterator<Item = &FromServer> {
+        self.pending.iter()
+    }
+}
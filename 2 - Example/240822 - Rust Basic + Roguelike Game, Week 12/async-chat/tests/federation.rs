@@ -0,0 +1,201 @@
+mod common;
+
+use async_chat::codec::WireFormat;
+use async_chat::rt;
+use async_chat::rt::net::SocketAddr;
+use async_chat::FromServer;
+use common::{start_server, TestClient, TestServer};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn start_alpha(federation_address: &str) -> TestServer {
+    let federation_address = federation_address.to_string();
+
+    start_server(|config| {
+        config.name = "alpha".to_string();
+        config.federation_address = Some(federation_address);
+    })
+    .await
+}
+
+async fn start_beta(peer: SocketAddr) -> TestServer {
+    start_server(|config| {
+        config.name = "beta".to_string();
+        config.peers.push(peer.to_string());
+    })
+    .await
+}
+
+/// Return the sender and text of the next message posted to `group_name`,
+/// skipping everything else.
+async fn receive_in(client: &mut TestClient, group_name: &str) -> (String, String) {
+    loop {
+        if let FromServer::Message {
+            group_name: group,
+            sender,
+            message,
+            ..
+        } = client.receive().await
+        {
+            if *group == group_name {
+                return (sender.to_string(), message.to_string());
+            }
+        }
+    }
+}
+
+/// Wait until the servers that `listener` and `pinger` are on are linked,
+/// by having `pinger` post to a group until `listener` hears about it.
+/// Both must already be in a group called "Lobby".
+async fn wait_for_link(pinger: TestClient, listener: &mut TestClient) {
+    let linked = Arc::new(AtomicBool::new(false));
+    let pinging = rt::spawn({
+        let linked = linked.clone();
+        let mut pinger = pinger;
+
+        async move {
+            while !linked.load(Ordering::Relaxed) {
+                pinger.post("Lobby", "ping").await;
+                rt::sleep(Duration::from_millis(50)).await;
+            }
+        }
+    });
+
+    receive_in(listener, "Lobby").await;
+    linked.store(true, Ordering::Relaxed);
+    pinging.await;
+}
+
+async fn connect(server: &TestServer, nickname: &str, groups: &[&str]) -> TestClient {
+    let mut client = TestClient::connect(server.address, nickname, WireFormat::Binary).await;
+
+    for group in groups {
+        client.join(group).await;
+    }
+
+    client
+}
+
+#[test]
+fn test_groups_are_mirrored_across_a_link() {
+    rt::block_on(async {
+        let alpha = start_alpha("127.0.0.1:0").await;
+        let beta = start_beta(alpha.federation_address.unwrap()).await;
+
+        let mut alice = connect(&alpha, "alice", &["Lobby", "Dogs"]).await;
+        let mut bob = connect(&beta, "bob", &["Lobby", "Dogs"]).await;
+        wait_for_link(connect(&alpha, "pinger", &["Lobby"]).await, &mut bob).await;
+
+        alice.post("Dogs", "Woof").await;
+        bob.post("Dogs", "Arf").await;
+
+        // Each post shows up once on each server, with remote senders
+        // named after their server.
+        assert_eq!(
+            receive_in(&mut alice, "Dogs").await,
+            ("alice".to_string(), "Woof".to_string())
+        );
+        assert_eq!(
+            receive_in(&mut alice, "Dogs").await,
+            ("bob@beta".to_string(), "Arf".to_string())
+        );
+
+        let mut seen_on_beta = vec![
+            receive_in(&mut bob, "Dogs").await,
+            receive_in(&mut bob, "Dogs").await,
+        ];
+        seen_on_beta.sort();
+        assert_eq!(
+            seen_on_beta,
+            [
+                ("alice@alpha".to_string(), "Woof".to_string()),
+                ("bob".to_string(), "Arf".to_string()),
+            ]
+        );
+
+        // Nothing was echoed back.
+        alice.post("Dogs", "Done").await;
+        assert_eq!(receive_in(&mut alice, "Dogs").await.1, "Done");
+        assert_eq!(receive_in(&mut bob, "Dogs").await.1, "Done");
+    });
+}
+
+#[test]
+fn test_link_recovers_when_a_peer_restarts() {
+    rt::block_on(async {
+        let alpha = start_alpha("127.0.0.1:0").await;
+        let federation_address = alpha.federation_address.unwrap();
+        let beta = start_beta(federation_address).await;
+
+        let mut bob = connect(&beta, "bob", &["Lobby", "Dogs"]).await;
+        wait_for_link(connect(&alpha, "pinger", &["Lobby"]).await, &mut bob).await;
+
+        alpha.shutdown.request();
+        alpha.task.await.unwrap();
+
+        // Come back on the same address; beta keeps trying until it gets through.
+        let alpha = start_alpha(&federation_address.to_string()).await;
+        let mut alice = connect(&alpha, "alice", &["Lobby", "Dogs"]).await;
+        wait_for_link(connect(&alpha, "pinger", &["Lobby"]).await, &mut bob).await;
+
+        bob.post("Dogs", "Welcome back").await;
+        assert_eq!(
+            receive_in(&mut alice, "Dogs").await,
+            ("bob@beta".to_string(), "Welcome back".to_string())
+        );
+    });
+}
+
+#[test]
+fn test_servers_that_dial_each_other_keep_one_link() {
+    rt::block_on(async {
+        // Each server must know the other's address before either starts.
+        let free_address = || {
+            std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+        };
+        let (alpha_address, beta_address) = (free_address(), free_address());
+
+        let alpha = start_server(|config| {
+            config.name = "alpha".to_string();
+            config.federation_address = Some(alpha_address.to_string());
+            config.peers.push(beta_address.to_string());
+        })
+        .await;
+        let beta = start_server(|config| {
+            config.name = "beta".to_string();
+            config.federation_address = Some(beta_address.to_string());
+            config.peers.push(alpha_address.to_string());
+        })
+        .await;
+
+        let mut alice = connect(&alpha, "alice", &["Lobby", "Dogs"]).await;
+        let mut bob = connect(&beta, "bob", &["Lobby", "Dogs"]).await;
+        wait_for_link(connect(&alpha, "pinger", &["Lobby"]).await, &mut bob).await;
+
+        // Long enough for a dropped link to be dialed again.
+        rt::sleep(Duration::from_secs(1)).await;
+
+        // One link carries each post exactly once, in both directions.
+        for round in 0..3 {
+            let woof = format!("Woof {round}");
+            alice.post("Dogs", &woof).await;
+            assert_eq!(receive_in(&mut alice, "Dogs").await.1, woof);
+            assert_eq!(
+                receive_in(&mut bob, "Dogs").await,
+                ("alice@alpha".to_string(), woof)
+            );
+
+            let arf = format!("Arf {round}");
+            bob.post("Dogs", &arf).await;
+            assert_eq!(receive_in(&mut bob, "Dogs").await.1, arf);
+            assert_eq!(
+                receive_in(&mut alice, "Dogs").await,
+                ("bob@beta".to_string(), arf)
+            );
+        }
+    });
+}
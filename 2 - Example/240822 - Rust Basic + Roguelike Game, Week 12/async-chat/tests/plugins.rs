@@ -0,0 +1,125 @@
+mod common;
+
+use async_chat::codec::WireFormat;
+use async_chat::rt;
+use async_chat::server::plugin::{Context, Plugin, Plugins, Verdict};
+use async_chat::FromServer;
+use common::{start_server_with_plugins, TestClient};
+use std::sync::Arc;
+
+/// Turns `!shout` posts into capitals, and says goodbye to whoever leaves.
+struct Shout;
+
+impl Plugin for Shout {
+    fn name(&self) -> &str {
+        "shout"
+    }
+
+    fn on_post(&self, _context: &mut Context, message: &mut Arc<String>) -> Verdict {
+        if let Some(rest) = message.strip_prefix("!shout ") {
+            *message = Arc::new(rest.to_uppercase());
+        }
+
+        Verdict::Deliver
+    }
+
+    fn on_leave(&self, context: &mut Context) {
+        context.reply_to_group(format!("Goodbye, {}", context.nickname()));
+    }
+}
+
+/// Return the next packet that isn't news about other members or
+/// a private notice.
+async fn receive_message(client: &mut TestClient) -> (String, String) {
+    loop {
+        match client.receive_reply().await {
+            FromServer::Message {
+                sender, message, ..
+            } => return (sender.to_string(), message.to_string()),
+            FromServer::Notice { .. } => continue,
+            other => panic!("expected a message, got {other:?}"),
+        }
+    }
+}
+
+async fn receive_notice(client: &mut TestClient) -> (String, String) {
+    loop {
+        match client.receive_reply().await {
+            FromServer::Notice { from, message, .. } => {
+                return (from.to_string(), message.to_string())
+            }
+            FromServer::Message { .. } => continue,
+            other => panic!("expected a notice, got {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_builtin_plugins() {
+    rt::block_on(async {
+        let server = start_server_with_plugins(Plugins::builtin(), |_| {}).await;
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Json).await;
+
+        alice.join("Dogs").await;
+        let (from, welcome) = receive_notice(&mut alice).await;
+        assert_eq!(from, "welcome");
+        assert!(welcome.contains("You're the first one here"));
+
+        bob.join("Dogs").await;
+        let (_, welcome) = receive_notice(&mut bob).await;
+        assert!(welcome.contains("There is 1 other member here"));
+
+        // `/who` is answered privately, and the group never sees it.
+        bob.post("Dogs", "/who").await;
+        assert_eq!(
+            receive_notice(&mut bob).await,
+            ("who".to_string(), "Members of Dogs: alice, bob".to_string())
+        );
+
+        // `/roll` is seen by everyone, followed by its result.
+        alice.post("Dogs", "/roll 3d6").await;
+        for client in [&mut alice, &mut bob] {
+            let (sender, command) = receive_message(client).await;
+            assert_eq!((sender.as_str(), command.as_str()), ("alice", "/roll 3d6"));
+
+            let (sender, result) = receive_message(client).await;
+            assert_eq!(sender, "roll");
+            assert!(result.starts_with("alice rolled 3d6: "), "{result}");
+        }
+
+        // Plugins' names are taken.
+        let mut impostor = TestClient::connect(server.address, "roll", WireFormat::Json).await;
+        assert!(matches!(
+            impostor.receive().await,
+            FromServer::Error(message) if message.contains("already taken")
+        ));
+    });
+}
+
+#[test]
+fn test_plugins_rewrite_posts_and_see_members_leave() {
+    rt::block_on(async {
+        let mut plugins = Plugins::new();
+        plugins.register(Shout);
+
+        let server = start_server_with_plugins(plugins, |_| {}).await;
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Binary).await;
+
+        alice.join("Dogs").await;
+        bob.join("Dogs").await;
+
+        bob.post("Dogs", "!shout hello").await;
+        assert_eq!(
+            receive_message(&mut alice).await,
+            ("bob".to_string(), "HELLO".to_string())
+        );
+
+        drop(bob);
+        assert_eq!(
+            receive_message(&mut alice).await,
+            ("shout".to_string(), "Goodbye, bob".to_string())
+        );
+    });
+}
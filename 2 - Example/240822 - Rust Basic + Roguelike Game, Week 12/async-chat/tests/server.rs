@@ -0,0 +1,314 @@
+mod common;
+
+use async_chat::codec::WireFormat;
+use async_chat::rt;
+use async_chat::server::rate_limit::RateLimit;
+use async_chat::{FromClient, FromServer};
+use common::{arc, start_server, TestClient};
+use std::time::Duration;
+
+#[test]
+fn test_posts_reach_every_member() {
+    rt::block_on(async {
+        let server = start_server(|_| {}).await;
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Binary).await;
+        let mut carol = TestClient::connect(server.address, "carol", WireFormat::Json).await;
+
+        alice.join("Dogs").await;
+        bob.join("Dogs").await;
+        carol.join("Cats").await;
+
+        // Connections are served concurrently, so make sure Woof is
+        // posted before Bark is sent.
+        alice.post("Dogs", "Woof").await;
+        alice.sync().await;
+        bob.post("Dogs", "Bark").await;
+
+        for client in [&mut alice, &mut bob] {
+            for (sequence, sender, text) in [(1, "alice", "Woof"), (2, "bob", "Bark")] {
+                match client.receive_reply().await {
+                    FromServer::Message {
+                        group_name,
+                        sender: from,
+                        message,
+                        sequence: number,
+                        ..
+                    } => {
+                        assert_eq!(*group_name, "Dogs");
+                        assert_eq!(*from, sender);
+                        assert_eq!(*message, text);
+                        assert_eq!(number, sequence);
+                    }
+                    other => panic!("expected a message, got {other:?}"),
+                }
+            }
+        }
+
+        // Nothing posted to Dogs may leak into Cats.
+        carol.sync().await;
+        assert!(!carol.pending().any(|packet| matches!(
+            packet,
+            FromServer::Message { group_name, .. } if **group_name == "Dogs"
+        )));
+    });
+}
+
+#[test]
+fn test_unknown_groups_are_reported() {
+    rt::block_on(async {
+        let server = start_server(|_| {}).await;
+        let mut client = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+
+        client.post("Nowhere", "Hello?").await;
+        client
+            .send(FromClient::Fetch {
+                group_name: arc("Nowhere"),
+                from: 1,
+                to: 10,
+            })
+            .await;
+
+        for _ in 0..2 {
+            assert_eq!(
+                client.receive().await,
+                FromServer::Error("Group 'Nowhere' does not exist".to_string())
+            );
+        }
+    });
+}
+
+#[test]
+fn test_disconnects_are_announced_and_free_the_nickname() {
+    rt::block_on(async {
+        let server = start_server(|_| {}).await;
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Json).await;
+
+        alice.join("Dogs").await;
+        bob.join("Dogs").await;
+        drop(bob);
+
+        let left = FromServer::MemberLeft {
+            group_name: arc("Dogs"),
+            nickname: arc("bob"),
+        };
+        while alice.receive().await != left {}
+
+        // The group is still there for those who stayed, and the name is free again.
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Json).await;
+        bob.join("Dogs").await;
+        bob.post("Dogs", "I'm back").await;
+
+        assert!(matches!(
+            alice.receive_reply().await,
+            FromServer::Message { sender, .. } if *sender == "bob"
+        ));
+    });
+}
+
+#[test]
+fn test_legacy_clients_get_only_messages_and_errors() {
+    rt::block_on(async {
+        let server = start_server(|_| {}).await;
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut old = TestClient::connect_legacy(server.address).await;
+
+        alice.join("Dogs").await;
+        old.send(FromClient::Join {
+            group_name: arc("Dogs"),
+        })
+        .await;
+        old.sync().await;
+
+        // Give the old client news it wouldn't understand: members
+        // coming, typing, and going.
+        let mut bob = TestClient::connect(server.address, "bob", WireFormat::Binary).await;
+        bob.join("Dogs").await;
+        bob.send(FromClient::Typing {
+            group_name: arc("Dogs"),
+        })
+        .await;
+        bob.sync().await;
+        drop(bob);
+
+        alice.post("Dogs", "Woof").await;
+        alice.sync().await;
+        old.post("Dogs", "Bark").await;
+
+        for text in ["Woof", "Bark"] {
+            assert!(matches!(
+                alice.receive_reply().await,
+                FromServer::Message { message, .. } if *message == text
+            ));
+        }
+
+        // Its first packet is the first message: no `Welcome`, and none
+        // of bob's comings and goings.
+        for (number, text) in [(1, "Woof"), (2, "Bark")] {
+            match old.receive().await {
+                FromServer::Message {
+                    message, sequence, ..
+                } => {
+                    assert_eq!(*message, text);
+                    assert_eq!(sequence, number);
+                }
+                other => panic!("expected a message, got {other:?}"),
+            }
+        }
+
+        old.sync().await;
+        assert!(old.pending().all(FromServer::is_legacy));
+    });
+}
+
+#[test]
+fn test_flooding_clients_run_out_of_strikes() {
+    const BURST: u32 = 2;
+    const STRIKES: u32 = 3;
+
+    rt::block_on(async {
+        // Nothing refills during the test: every post past the burst is
+        // a strike, and no strike is forgiven.
+        let server = start_server(|config| {
+            config.limits.connection = RateLimit {
+                per_second: 1e-6,
+                burst: BURST,
+            };
+            config.limits.max_strikes = STRIKES;
+            config.limits.forgive_after = Duration::from_secs(3600);
+        })
+        .await;
+
+        let mut alice = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        let mut flooder = TestClient::connect(server.address, "flooder", WireFormat::Json).await;
+        alice.join("Dogs").await;
+        flooder.join("Dogs").await;
+
+        for _ in 0..BURST + STRIKES + 1 {
+            flooder.post("Dogs", "Woof").await;
+        }
+
+        let mut replies = Vec::new();
+        while let Some(packet) = flooder.try_receive().await {
+            match packet {
+                FromServer::Error(message) => replies.push(message),
+                FromServer::Message { .. } => {}
+                other => panic!("expected a message or an error, got {other:?}"),
+            }
+        }
+
+        let too_fast = "You are posting too fast; message dropped";
+        assert_eq!(replies.len(), STRIKES as usize + 1);
+        assert!(replies[..STRIKES as usize]
+            .iter()
+            .all(|reply| reply == too_fast));
+        assert_eq!(replies[STRIKES as usize], "Disconnected for flooding");
+
+        // Only the burst got through before the connection was dropped.
+        let left = FromServer::MemberLeft {
+            group_name: arc("Dogs"),
+            nickname: arc("flooder"),
+        };
+        let mut posts = 0;
+        loop {
+            match alice.receive().await {
+                FromServer::Message { .. } => posts += 1,
+                packet if packet == left => break,
+                _ => {}
+            }
+        }
+        assert_eq!(posts, BURST);
+    });
+}
+
+#[test]
+fn test_lagging_subscriber_can_fetch_what_it_missed() {
+    const HISTORY: usize = 16;
+    const POSTS: u64 = 400;
+
+    rt::block_on(async {
+        let server = start_server(|config| {
+            let unlimited = RateLimit {
+                per_second: 1e6,
+                burst: 1_000_000,
+            };
+            config.limits.connection = unlimited;
+            config.limits.group = unlimited;
+            config.history = HISTORY;
+        })
+        .await;
+
+        let mut reader = TestClient::connect(server.address, "reader", WireFormat::Binary).await;
+        let mut writer = TestClient::connect(server.address, "writer", WireFormat::Binary).await;
+        reader.join("Dogs").await;
+        writer.join("Dogs").await;
+
+        // Far more than the socket buffers hold, so while the reader isn't
+        // reading, its subscription falls more than `HISTORY` messages behind.
+        let big = "x".repeat(64 * 1024);
+        for _ in 0..POSTS {
+            writer.post("Dogs", &big).await;
+        }
+        writer.sync().await;
+
+        let mut expected = 1;
+        let mut gap = None;
+
+        while expected <= POSTS {
+            let sequence = match reader.receive_reply().await {
+                FromServer::Message { sequence, .. } => sequence,
+                other => panic!("expected a message, got {other:?}"),
+            };
+
+            if sequence != expected && gap.is_none() {
+                gap = Some((expected, sequence - 1));
+            }
+
+            expected = sequence + 1;
+        }
+
+        // How much is skipped depends on how the reader's subscription
+        // was scheduled, but the gap starts long before the oldest message
+        // the group still keeps.
+        let (from, _to) = gap.expect("the reader never fell behind");
+        let oldest = POSTS - HISTORY as u64 + 1;
+        assert!(from < oldest);
+
+        // Whatever is still kept comes back.
+        reader
+            .send(FromClient::Fetch {
+                group_name: arc("Dogs"),
+                from,
+                to: POSTS,
+            })
+            .await;
+
+        for sequence in oldest..=POSTS {
+            assert!(matches!(
+                reader.receive_reply().await,
+                FromServer::Message { sequence: number, .. } if number == sequence
+            ));
+        }
+
+        assert!(matches!(
+            reader.receive_reply().await,
+            FromServer::Error(message) if message.contains("no longer available")
+        ));
+    });
+}
+
+#[test]
+fn test_shutdown_says_goodbye() {
+    rt::block_on(async {
+        let server = start_server(|_| {}).await;
+        let mut client = TestClient::connect(server.address, "alice", WireFormat::Json).await;
+        client.join("Dogs").await;
+
+        server.shutdown.request();
+
+        assert_eq!(client.receive_reply().await, FromServer::ShuttingDown);
+        assert_eq!(client.try_receive().await, None);
+        server.task.await.unwrap();
+    });
+}
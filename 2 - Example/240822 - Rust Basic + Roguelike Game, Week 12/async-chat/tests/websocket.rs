@@ -0,0 +1,113 @@
+mod common;
+
+use async_chat::codec::WireFormat;
+use async_chat::rt;
+use async_chat::rt::net::{SocketAddr, TcpStream};
+use async_chat::{FromClient, FromServer};
+use async_tungstenite::tungstenite::Message;
+use async_tungstenite::WebSocketStream;
+use common::{arc, start_server, TestClient};
+use futures::prelude::*;
+use std::time::Duration;
+
+/// A browser-like client, speaking JSON text messages.
+struct WebSocketClient(WebSocketStream<TcpStream>);
+
+impl WebSocketClient {
+    async fn connect(address: SocketAddr) -> WebSocketClient {
+        let socket = TcpStream::connect(address).await.unwrap();
+        let url = format!("ws://{address}");
+        let (socket, _response) = async_tungstenite::client_async(url, socket).await.unwrap();
+
+        WebSocketClient(socket)
+    }
+
+    async fn send(&mut self, request: FromClient) {
+        let json = serde_json::to_string(&request).unwrap();
+        self.0.send(Message::text(json)).await.unwrap();
+    }
+
+    async fn receive(&mut self) -> FromServer {
+        let message = rt::timeout(Duration::from_secs(5), self.0.next())
+            .await
+            .expect("timed out waiting for the server")
+            .expect("the server closed the connection")
+            .unwrap();
+
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+}
+
+#[test]
+fn test_messages_cross_between_transports() {
+    rt::block_on(async {
+        let server = start_server(|config| {
+            config.websocket_address = Some("127.0.0.1:0".to_string());
+        })
+        .await;
+
+        let mut browser = WebSocketClient::connect(server.websocket_address.unwrap()).await;
+        let mut terminal =
+            TestClient::connect(server.address, "terminal", WireFormat::Binary).await;
+
+        browser
+            .send(FromClient::SetNickname {
+                nickname: arc("browser"),
+            })
+            .await;
+        browser
+            .send(FromClient::Join {
+                group_name: arc("Dogs"),
+            })
+            .await;
+        assert_eq!(
+            browser.receive().await,
+            FromServer::MemberJoined {
+                group_name: arc("Dogs"),
+                nickname: arc("browser"),
+            }
+        );
+
+        terminal.join("Dogs").await;
+        assert!(matches!(
+            browser.receive().await,
+            FromServer::MemberJoined { nickname, .. } if *nickname == "terminal"
+        ));
+
+        browser
+            .send(FromClient::Post {
+                group_name: arc("Dogs"),
+                message: arc("Hello from the web"),
+            })
+            .await;
+        assert!(matches!(
+            terminal.receive_reply().await,
+            FromServer::Message { message, .. } if *message == "Hello from the web"
+        ));
+
+        terminal.post("Dogs", "Hello from the terminal").await;
+        assert!(matches!(
+            terminal.receive_reply().await,
+            FromServer::Message { message, .. } if *message == "Hello from the terminal"
+        ));
+
+        for client_message in ["Hello from the web", "Hello from the terminal"] {
+            assert!(matches!(
+                browser.receive().await,
+                FromServer::Message { message, .. } if *message == client_message
+            ));
+        }
+
+        // Mistakes are reported the same way as over TCP.
+        browser
+            .send(FromClient::Post {
+                group_name: arc("Nowhere"),
+                message: arc("Hello?"),
+            })
+            .await;
+        assert_eq!(
+            browser.receive().await,
+            FromServer::Error("Group 'Nowhere' does not exist".to_string())
+        );
+    });
+}
@@ -0,0 +1,73 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use mandelbrot::simd::{escape_times, escape_times_fallback, LANES};
+use mandelbrot::{escape_time, pixel_to_point};
+use num::Complex;
+
+const BOUNDS: (usize, usize) = (400, 300);
+const LIMIT: usize = 255;
+
+fn kernels(c: &mut Criterion) {
+    let upper_left = Complex {
+        re: -1.20,
+        im: 0.35,
+    };
+    let lower_right = Complex {
+        re: -1.00,
+        im: 0.20,
+    };
+    let points = (0..BOUNDS.0 * BOUNDS.1)
+        .map(|i| {
+            pixel_to_point(
+                BOUNDS,
+                (i % BOUNDS.0, i / BOUNDS.0),
+                upper_left,
+                lower_right,
+            )
+        })
+        .collect::<Vec<_>>();
+    let groups = points
+        .chunks_exact(LANES)
+        .map(|group| <[Complex<f64>; LANES]>::try_from(group).unwrap())
+        .collect::<Vec<_>>();
+
+    // A fast kernel that gets the wrong answer isn't worth timing.
+    for &group in &groups {
+        assert_eq!(
+            escape_times(group, LIMIT),
+            group.map(|point| escape_time(point, LIMIT))
+        );
+    }
+
+    let mut group = c.benchmark_group(format!("escape_time {}x{}", BOUNDS.0, BOUNDS.1));
+    group.sample_size(10);
+
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            points
+                .iter()
+                .map(|&point| escape_time(point, LIMIT))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("fallback", |b| {
+        b.iter(|| {
+            groups
+                .iter()
+                .map(|&group| escape_times_fallback(group, LIMIT))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            groups
+                .iter()
+                .map(|&group| escape_times(group, LIMIT))
+                .collect::<Vec<_>>()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, kernels);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mandelbrot::color::Coloring;
+use mandelbrot::view::View;
+use mandelbrot::{cli, strategy};
+use num::Complex;
+
+const BOUNDS: (usize, usize) = (800, 600);
+
+fn strategies(c: &mut Criterion) {
+    let view = View::new(
+        BOUNDS,
+        Complex {
+            re: -1.20,
+            im: 0.35,
+        },
+        Complex {
+            re: -1.00,
+            im: 0.20,
+        },
+    );
+    let coloring = Coloring::default();
+    let threads = cli::default_threads();
+    let strategies = strategy::all(threads);
+
+    // A fast strategy that draws the wrong picture isn't worth timing.
+    let expected = coloring.render(&strategy::Single, &view);
+
+    for strategy in &strategies {
+        assert!(
+            coloring.render(strategy.as_ref(), &view) == expected,
+            "{} renders different pixels",
+            strategy.name()
+        );
+    }
+
+    let mut group = c.benchmark_group(format!("{}x{}", BOUNDS.0, BOUNDS.1));
+    group.sample_size(10);
+
+    for strategy in &strategies {
+        group.bench_function(BenchmarkId::new(strategy.name(), threads), |b| {
+            b.iter(|| coloring.render(strategy.as_ref(), &view))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, strategies);
+criterion_main!(benches);
@@ -0,0 +1,169 @@
+//! Zooming in on a point, one frame at a time.
+//!
+//! Each frame is narrower than the one before by the same factor, so the
+//! zoom looks steady however deep it goes. The center drifts from the
+//! start view's center to the target as the frames narrow, arriving with
+//! the last one.
+//!
+//! Frames are written as a numbered sequence of PNG files in a directory,
+//! which video tools can read directly. Any frame already in the
+//! directory is kept, so a long render that was interrupted picks up
+//! where it stopped. The directory also records what the frames show,
+//! so that a different flight doesn't carry on from them.
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageResult};
+use num::Complex;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::color::Coloring;
+use crate::perturbation::Decimal;
+use crate::view::View;
+use crate::RenderStrategy;
+
+/// The file in a frame directory that records the flight its frames
+/// belong to.
+const PARAMETERS: &str = "flight.txt";
+
+#[derive(Debug)]
+pub struct Flight {
+    /// The first frame.
+    pub start: View,
+    /// The point the last frame is centered on, to as many digits as it
+    /// was given.
+    pub target: Complex<Decimal>,
+    /// How many times narrower the last frame is than the first.
+    pub zoom: f64,
+    pub frames: usize,
+}
+
+impl Flight {
+    /// The view drawn in frame `index`, counting from zero.
+    pub fn frame(&self, index: usize) -> View {
+        let t = if self.frames > 1 {
+            index as f64 / (self.frames - 1) as f64
+        } else {
+            0.0
+        };
+
+        // How wide this frame is, and the last one, compared to the first.
+        let scale = self.zoom.powf(-t);
+        let end = 1.0 / self.zoom;
+        // How far the center still has to go to reach the target. It moves
+        // in step with the width, so it doesn't race ahead of the zoom and
+        // leave the target off screen while the frames are still wide.
+        let remaining = if end == 1.0 {
+            1.0 - t
+        } else {
+            (scale - end) / (1.0 - end)
+        };
+
+        let start = &self.start;
+        let (width, height) = start.size();
+        let center = |from: &Decimal, to: &Decimal, target: &Decimal| {
+            target + &(&from.midpoint(to) - target).scale(remaining)
+        };
+        let center = Complex {
+            re: center(&start.upper_left.re, &start.lower_right.re, &self.target.re),
+            im: center(&start.upper_left.im, &start.lower_right.im, &self.target.im),
+        };
+        let half_width = Decimal::from(width * scale / 2.0);
+        let half_height = Decimal::from(height * scale / 2.0);
+
+        View {
+            upper_left: Complex {
+                re: &center.re - &half_width,
+                im: &center.im + &half_height,
+            },
+            lower_right: Complex {
+                re: &center.re + &half_width,
+                im: &center.im - &half_height,
+            },
+            ..start.clone()
+        }
+    }
+
+    /// Render every frame that isn't in `directory` yet, creating it if
+    /// need be, and return how many were rendered. Fail if the frames
+    /// already there were rendered for another flight or coloring.
+    pub fn render(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        coloring: &Coloring,
+        directory: &Path,
+    ) -> ImageResult<usize> {
+        fs::create_dir_all(directory)?;
+        self.claim(coloring, directory)?;
+
+        let mut rendered = 0;
+
+        for index in 0..self.frames {
+            let path = frame_path(directory, index);
+
+            if path.exists() {
+                continue;
+            }
+
+            // Written under another name first, so a frame cut off halfway
+            // isn't mistaken for a finished one next time.
+            let partial = path.with_extension("part.png");
+            coloring.write(strategy, &self.frame(index), &partial.to_string_lossy())?;
+            fs::rename(&partial, &path)?;
+
+            rendered += 1;
+        }
+
+        Ok(rendered)
+    }
+
+    /// Record in `directory` that its frames show this flight drawn with
+    /// `coloring`, or check that they already do. Every frame looks the
+    /// same whichever strategy draws it, so that isn't recorded.
+    fn claim(&self, coloring: &Coloring, directory: &Path) -> io::Result<()> {
+        let parameters = format!("{self:#?}\n{coloring:#?}\n");
+        let path = directory.join(PARAMETERS);
+
+        let matches = match fs::read_to_string(&path) {
+            Ok(recorded) => recorded == parameters,
+            // Frames from before anything was recorded can't be trusted.
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                !(0..self.frames).any(|index| frame_path(directory, index).exists())
+            }
+            Err(error) => return Err(error),
+        };
+
+        if !matches {
+            return Err(io::Error::other(format!(
+                "{} holds frames of a different flight; remove them or choose another directory",
+                directory.display()
+            )));
+        }
+
+        fs::write(path, parameters)
+    }
+
+    /// Gather the frames in `directory` into an animated GIF at `path`,
+    /// playing `fps` frames a second and looping forever.
+    pub fn write_gif(&self, directory: &Path, path: &Path, fps: u32) -> ImageResult<()> {
+        let mut encoder = GifEncoder::new(File::create(path)?);
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        for index in 0..self.frames {
+            let image = image::open(frame_path(directory, index))?.to_rgba8();
+            let delay = Delay::from_numer_denom_ms(1000, fps);
+
+            encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where frame `index` is written in `directory`: `frame-00000.png` and
+/// so on, in an order that sorts the same as a number or as text.
+pub fn frame_path(directory: &Path, index: usize) -> PathBuf {
+    directory.join(format!("frame-{index:05}.png"))
+}
@@ -0,0 +1,89 @@
+//! A lock-free dispenser of mutable chunks.
+//!
+//! `AtomicChunksMut` takes the `&mut` borrow of a whole slice and keeps it
+//! as a raw pointer. Threads share the dispenser and claim chunks from it
+//! by advancing an atomic cursor; since each compare-and-swap claims a
+//! range nobody else can claim, the `&mut` chunks it hands out never
+//! overlap, just like those from `chunks_mut`.
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+#[cfg(loom)]
+use loom::sync::atomic::AtomicUsize;
+#[cfg(not(loom))]
+use std::sync::atomic::AtomicUsize;
+
+use std::sync::atomic::Ordering::*;
+
+/// Hands out `step`-sized chunks of a slice to whichever thread asks next,
+/// along with each chunk's index. Iterate over a shared reference to it,
+/// from as many threads as you like.
+pub struct AtomicChunksMut<'a, T> {
+    start: NonNull<T>,
+    len: usize,
+    step: usize,
+    next: AtomicUsize,
+    // We hold the slice's `&mut` borrow, even though we only keep a pointer.
+    _slice: PhantomData<&'a mut [T]>,
+}
+
+// Sharing the dispenser lets other threads take `&mut T`s out of it, which
+// is sending the elements to them; so does moving it, like `&mut [T]`.
+unsafe impl<T: Send> Send for AtomicChunksMut<'_, T> {}
+unsafe impl<T: Send> Sync for AtomicChunksMut<'_, T> {}
+
+impl<'a, T> AtomicChunksMut<'a, T> {
+    /// Panics if `step` is zero.
+    pub fn new(slice: &'a mut [T], step: usize) -> AtomicChunksMut<'a, T> {
+        assert!(step != 0, "chunk size must be non-zero");
+
+        AtomicChunksMut {
+            len: slice.len(),
+            start: NonNull::from(slice).cast(),
+            step,
+            next: AtomicUsize::new(0),
+            _slice: PhantomData,
+        }
+    }
+
+    /// Claim the next chunk, or return `None` once they've all been taken.
+    pub fn claim(&self) -> Option<(usize, &'a mut [T])> {
+        let mut current = self.next.load(Relaxed);
+
+        let end = loop {
+            if current == self.len {
+                return None;
+            }
+
+            let end = current + std::cmp::min(self.step, self.len - current);
+
+            // Only the cursor's own value matters here: the chunk's memory
+            // was handed to us with the borrow, not by another thread.
+            match self
+                .next
+                .compare_exchange_weak(current, end, Relaxed, Relaxed)
+            {
+                Ok(_) => break end,
+                Err(actual) => current = actual,
+            }
+        };
+
+        // SAFETY: `current..end` lies within the slice we borrowed for `'a`,
+        // and the compare-and-swap moved the cursor past it, so no other
+        // call can return any part of it.
+        let chunk = unsafe {
+            std::slice::from_raw_parts_mut(self.start.as_ptr().add(current), end - current)
+        };
+
+        Some((current / self.step, chunk))
+    }
+}
+
+impl<'a, T> Iterator for &AtomicChunksMut<'a, T> {
+    type Item = (usize, &'a mut [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.claim()
+    }
+}
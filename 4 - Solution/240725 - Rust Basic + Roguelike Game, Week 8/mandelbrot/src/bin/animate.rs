@@ -0,0 +1,86 @@
+use mandelbrot::animation::Flight;
+use mandelbrot::cli::{self, Usage};
+use mandelbrot::parse_pair;
+use mandelbrot::view::View;
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let mut args = env::args().collect::<Vec<_>>();
+    let program = args.remove(0);
+    let usage = Usage {
+        options: format!(
+            "[--strategy NAME] [--threads N] [--gif FILE] [--fps N] {}",
+            cli::OPTIONS
+        ),
+        arguments: "DIRECTORY PIXELS UPPER_LEFT LOWER_RIGHT TARGET ZOOM FRAMES",
+        example: "frames 640x480 -2.2,1.2 1.0,-1.2 -0.743643887037151,0.131825904205330 1e6 240",
+    };
+
+    let options = cli::take_options(&program, &mut args, &usage);
+    let strategy = cli::take_strategy(&program, &mut args, &usage);
+
+    let mut gif = None;
+    let mut fps = 30;
+
+    while let Some(i) = args.iter().position(|arg| arg == "--gif" || arg == "--fps") {
+        if i + 1 == args.len() {
+            usage.exit(&program);
+        }
+
+        let value = args.remove(i + 1);
+
+        match args.remove(i).as_str() {
+            "--gif" => gif = Some(value),
+            _ => fps = value.parse().expect("Error parsing frame rate"),
+        }
+    }
+
+    if args.len() != 7 || args.iter().any(|arg| arg.starts_with("--")) {
+        usage.exit(&program);
+    }
+
+    let bounds = parse_pair(&args[1], 'x').expect("Error parsing image dimensions");
+    let upper_left =
+        cli::parse_decimal_complex(&args[2]).expect("Error parsing upper left corner point");
+    let lower_right =
+        cli::parse_decimal_complex(&args[3]).expect("Error parsing lower right corner point");
+    cli::check_corners(&upper_left, &lower_right);
+    let target = cli::parse_decimal_complex(&args[4]).expect("Error parsing target point");
+    let zoom: f64 = args[5].parse().expect("Error parsing zoom");
+    let frames = args[6].parse().expect("Error parsing frame count");
+
+    if !zoom.is_finite() || zoom <= 0.0 || frames == 0 || fps == 0 {
+        usage.exit(&program);
+    }
+
+    let flight = Flight {
+        start: View {
+            fractal: options.fractal,
+            bounds,
+            upper_left,
+            lower_right,
+            limit: options.limit,
+        },
+        target,
+        zoom,
+        frames,
+    };
+
+    let directory = Path::new(&args[0]);
+    let rendered = flight
+        .render(strategy.as_ref(), &options.coloring, directory)
+        .expect("Error writing frame");
+
+    println!(
+        "Rendered {rendered} of {frames} frames; the rest were already in {}",
+        directory.display()
+    );
+
+    if let Some(gif) = gif {
+        flight
+            .write_gif(directory, Path::new(&gif), fps)
+            .expect("Error writing GIF file");
+    }
+}
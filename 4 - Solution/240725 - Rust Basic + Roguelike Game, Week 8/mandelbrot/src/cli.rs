@@ -0,0 +1,227 @@
+//! The command line shared by the `mandelbrot-*` programs.
+
+use num::Complex;
+
+use std::env;
+use std::sync::Arc;
+use std::thread;
+
+use crate::color::{Coloring, Palette, PALETTES};
+use crate::fractal::{self, Fractal, Mandelbrot, FRACTALS};
+use crate::perturbation::Decimal;
+use crate::view::{View, DEFAULT_LIMIT};
+use crate::{parse_pair, strategy, RenderStrategy};
+
+/// The flags every program accepts.
+pub const OPTIONS: &str = "[--fractal SPEC] [--limit N] \
+    [--palette NAME | --palette-file FILE] [--smooth] [--equalize] \
+    [--supersample N [--jitter] [--adaptive]] ";
+
+/// What the flags in `OPTIONS` ask for.
+pub struct Options {
+    pub fractal: Arc<dyn Fractal>,
+    pub coloring: Coloring,
+    pub limit: usize,
+}
+
+/// How to call a program, shown when it's called wrongly.
+pub struct Usage {
+    /// The flags, shown between the program's name and its arguments.
+    pub options: String,
+    pub arguments: &'static str,
+    /// Arguments that draw something worth looking at.
+    pub example: &'static str,
+}
+
+impl Usage {
+    /// The usage of a program that draws one image, and takes `options`.
+    pub fn image(options: &str) -> Usage {
+        Usage {
+            options: options.to_string(),
+            arguments: "FILE PIXELS UPPER_LEFT LOWER_RIGHT",
+            example: "mandel.png 1000x750 -1.20,0.35 -1.00,0.20",
+        }
+    }
+
+    /// Print how to call `program` and exit.
+    pub fn exit(&self, program: &str) -> ! {
+        eprintln!("Usage: {program} {}{}", self.options, self.arguments);
+        eprintln!("Example: {program} {}", self.example);
+        eprintln!("Fractals: {}", FRACTALS.join(", "));
+        eprintln!("Palettes: {}", PALETTES.join(", "));
+        eprintln!("Strategies: {}", strategy::NAMES.join(", "));
+
+        std::process::exit(-1);
+    }
+}
+
+/// How many threads to use when the user doesn't say.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(8, |n| n.get())
+}
+
+/// Remove the flags in `OPTIONS` from `args` and return what they ask
+/// for. Any other flags are left where they were.
+pub fn take_options(program: &str, args: &mut Vec<String>, usage: &Usage) -> Options {
+    let mut fractal: Arc<dyn Fractal> = Arc::new(Mandelbrot);
+    let mut coloring = Coloring::default();
+    let mut limit = DEFAULT_LIMIT;
+    let mut i = 0;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--smooth" => coloring.smooth = true,
+            "--equalize" => coloring.equalize = true,
+            "--jitter" => coloring.supersampling.jitter = true,
+            "--adaptive" => coloring.supersampling.adaptive = true,
+            "--fractal" | "--limit" | "--palette" | "--palette-file" | "--supersample"
+                if i + 1 == args.len() =>
+            {
+                usage.exit(program)
+            }
+            "--fractal" => {
+                fractal = fractal::parse(&args.remove(i + 1)).unwrap_or_else(|error| {
+                    eprintln!("Error choosing fractal: {error}");
+                    std::process::exit(-1);
+                });
+            }
+            "--limit" => {
+                limit = args
+                    .remove(i + 1)
+                    .parse()
+                    .expect("Error parsing iteration limit");
+            }
+            "--supersample" => {
+                coloring.supersampling.grid = args
+                    .remove(i + 1)
+                    .parse()
+                    .ok()
+                    .filter(|&grid| grid >= 1)
+                    .expect("Error parsing supersampling grid size");
+            }
+            "--palette" => {
+                let name = args.remove(i + 1);
+
+                coloring.palette = Palette::named(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "Unknown palette {name:?}; choose one of: {}",
+                        PALETTES.join(", ")
+                    );
+                    std::process::exit(-1);
+                });
+            }
+            "--palette-file" => {
+                coloring.palette = Palette::load(&args.remove(i + 1)).unwrap_or_else(|error| {
+                    eprintln!("Error loading palette: {error}");
+                    std::process::exit(-1);
+                });
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+
+        args.remove(i);
+    }
+
+    Options {
+        fractal,
+        coloring,
+        limit,
+    }
+}
+
+/// Remove the `--strategy NAME` and `--threads N` flags from `args`, and
+/// return the strategy they ask for. Without them, that's `atomic-chunks`
+/// with a thread per core.
+pub fn take_strategy(
+    program: &str,
+    args: &mut Vec<String>,
+    usage: &Usage,
+) -> Box<dyn RenderStrategy<f32>> {
+    let mut name = String::from("atomic-chunks");
+    let mut threads = default_threads();
+    let mut i = 0;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--strategy" | "--threads" if i + 1 == args.len() => usage.exit(program),
+            "--strategy" => name = args.remove(i + 1),
+            "--threads" => {
+                threads = args
+                    .remove(i + 1)
+                    .parse()
+                    .expect("Error parsing thread count");
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        }
+
+        args.remove(i);
+    }
+
+    strategy::by_name(&name, threads).unwrap_or_else(|| {
+        eprintln!(
+            "Unknown strategy {name:?}; choose one of: {}",
+            strategy::NAMES.join(", ")
+        );
+        std::process::exit(-1);
+    })
+}
+
+/// Run a program which always renders with `strategy`, and only takes
+/// the flags in `OPTIONS`.
+pub fn run(strategy: &dyn RenderStrategy<f32>) {
+    let mut args = env::args().collect::<Vec<_>>();
+    let program = args.remove(0);
+    let usage = Usage::image(OPTIONS);
+    let options = take_options(&program, &mut args, &usage);
+
+    if args.len() != 4 || args.iter().any(|arg| arg.starts_with("--")) {
+        usage.exit(&program);
+    }
+
+    render_to_file(strategy, &options, &args);
+}
+
+/// Render the image described by `args`, which are the FILE, PIXELS,
+/// UPPER_LEFT and LOWER_RIGHT arguments, and write it out.
+pub fn render_to_file(strategy: &dyn RenderStrategy<f32>, options: &Options, args: &[String]) {
+    let bounds = parse_pair(&args[1], 'x').expect("Error parsing image dimensions");
+    // Parsed exactly, since deep zooms need more digits than `f64` keeps.
+    let upper_left =
+        parse_decimal_complex(&args[2]).expect("Error parsing upper left corner point");
+    let lower_right =
+        parse_decimal_complex(&args[3]).expect("Error parsing lower right corner point");
+    check_corners(&upper_left, &lower_right);
+
+    let view = View {
+        fractal: options.fractal.clone(),
+        bounds,
+        upper_left,
+        lower_right,
+        limit: options.limit,
+    };
+
+    options
+        .coloring
+        .write(strategy, &view, &args[0])
+        .expect("Error writing PNG file");
+}
+
+/// Exit with an error unless `upper_left` is above and to the left of
+/// `lower_right`, so that the view between them isn't empty.
+pub fn check_corners(upper_left: &Complex<Decimal>, lower_right: &Complex<Decimal>) {
+    if upper_left.re >= lower_right.re || upper_left.im <= lower_right.im {
+        eprintln!("Error: the upper left corner must be above and to the left of the lower right");
+        std::process::exit(-1);
+    }
+}
+
+/// Like `parse_complex`, but keeping every digit.
+pub fn parse_decimal_complex(s: &str) -> Option<Complex<Decimal>> {
+    parse_pair(s, ',').map(|(re, im)| Complex { re, im })
+}
@@ -0,0 +1,468 @@
+//! Coloring the Mandelbrot set.
+//!
+//! Rendering in color takes two passes. The first, `View::sample`, records
+//! a sample for each pixel: how many iterations its point took to escape,
+//! or NaN if it never did. The second looks each sample up in a palette. That pass runs
+//! on one thread, because histogram equalization needs the whole image's
+//! samples before it can color any of them.
+//!
+//! Images too big to keep in memory are rendered and written a band of
+//! rows at a time. Equalization then needs a pass of its own over the
+//! whole image first, counting samples without keeping them.
+
+use image::{ExtendedColorType, ImageResult};
+
+use std::f64::consts::LN_2;
+use std::fs;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::supersample::Supersampling;
+use crate::view::View;
+use crate::{write_image, write_image_bands, write_rgb_image, RenderStrategy};
+
+/// About how many points `Coloring::write` samples at once. Bigger images
+/// are written a band of rows at a time.
+pub const BAND_POINTS: usize = 1 << 22;
+
+/// The names accepted by [`Palette::named`].
+pub const PALETTES: [&str; 4] = ["gray", "fire", "ocean", "rainbow"];
+
+/// A gradient through evenly spaced colors. Points that escape at once
+/// get the first color, and those that take the most iterations the last.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    stops: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Panics if there are fewer than two colors.
+    pub fn new(stops: Vec<[u8; 3]>) -> Palette {
+        assert!(stops.len() >= 2, "a palette needs at least two colors");
+
+        Palette { stops }
+    }
+
+    /// One of the palettes listed in [`PALETTES`].
+    pub fn named(name: &str) -> Option<Palette> {
+        let stops = match name {
+            // Matches the grayscale `render` draws.
+            "gray" => vec![[255, 255, 255], [0, 0, 0]],
+            "fire" => vec![
+                [0, 0, 0],
+                [128, 0, 0],
+                [255, 64, 0],
+                [255, 200, 0],
+                [255, 255, 255],
+            ],
+            "ocean" => vec![
+                [0, 7, 100],
+                [32, 107, 203],
+                [237, 255, 255],
+                [255, 170, 0],
+                [0, 2, 0],
+            ],
+            "rainbow" => vec![
+                [255, 0, 0],
+                [255, 255, 0],
+                [0, 255, 0],
+                [0, 255, 255],
+                [0, 0, 255],
+                [255, 0, 255],
+            ],
+            _ => return None,
+        };
+
+        Some(Palette::new(stops))
+    }
+
+    /// Parse a gradient file: one color per line, as red, green and blue
+    /// values from 0 to 255 separated by spaces. Blank lines, and lines
+    /// starting with `#`, are ignored.
+    pub fn parse(text: &str) -> Result<Palette, String> {
+        let mut stops = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let channels = line
+                .split_whitespace()
+                .map(u8::from_str)
+                .collect::<Result<Vec<_>, _>>();
+
+            match channels.as_deref() {
+                Ok(&[red, green, blue]) => stops.push([red, green, blue]),
+                _ => {
+                    return Err(format!(
+                        "line {}: expected three numbers from 0 to 255",
+                        number + 1
+                    ))
+                }
+            }
+        }
+
+        if stops.len() < 2 {
+            return Err(String::from("a palette needs at least two colors"));
+        }
+
+        Ok(Palette { stops })
+    }
+
+    /// Read and parse the gradient file at `path`.
+    pub fn load(path: &str) -> Result<Palette, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("{path}: {error}"))?;
+
+        Palette::parse(&text).map_err(|error| format!("{path}: {error}"))
+    }
+
+    /// Whether every color is a shade of gray, so an image colored with
+    /// this palette can be saved with one channel instead of three.
+    pub fn is_gray(&self) -> bool {
+        self.stops
+            .iter()
+            .all(|&[red, green, blue]| red == green && green == blue)
+    }
+
+    /// The color a fraction `t` of the way along the gradient.
+    pub fn color(&self, t: f64) -> [u8; 3] {
+        let scaled = t.clamp(0.0, 1.0) * (self.stops.len() - 1) as f64;
+        let index = (scaled as usize).min(self.stops.len() - 2);
+        let fraction = scaled - index as f64;
+
+        let (from, to) = (self.stops[index], self.stops[index + 1]);
+
+        std::array::from_fn(|channel| {
+            let (from, to) = (from[channel] as f64, to[channel] as f64);
+
+            (from + (to - from) * fraction).round() as u8
+        })
+    }
+}
+
+/// How to turn samples into colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coloring {
+    pub palette: Palette,
+    /// Record fractional iteration counts, which blend the bands of
+    /// color into one another.
+    pub smooth: bool,
+    /// Spread the colors so each one covers about as many pixels as any
+    /// other, however the iteration counts are distributed.
+    pub equalize: bool,
+    /// How many points to color in each pixel.
+    pub supersampling: Supersampling,
+}
+
+impl Default for Coloring {
+    /// The same grayscale as `render`.
+    fn default() -> Coloring {
+        Coloring {
+            palette: Palette::named("gray").unwrap(),
+            smooth: false,
+            equalize: false,
+            supersampling: Supersampling::default(),
+        }
+    }
+}
+
+impl Coloring {
+    /// Render `view`, sampling it as `strategy` sees fit, and return its
+    /// pixels as red, green and blue bytes.
+    ///
+    /// With supersampling, each pixel is the average of its points' colors.
+    /// Histogram equalization still only counts one point per pixel, so
+    /// the colors don't shift with how many points were taken.
+    pub fn render(&self, strategy: &dyn RenderStrategy<f32>, view: &View) -> Vec<u8> {
+        let samples = view.sample(strategy, self.smooth);
+        let mut histogram = Histogram::new(view.limit);
+        histogram.add(&samples);
+
+        self.finish(strategy, view, 0..view.bounds.1, samples, &histogram)
+    }
+
+    /// Like `render`, for only the pixels in `rows`. To color them as
+    /// `render` would, `histogram` must count the whole image's samples,
+    /// as `Coloring::histogram` does.
+    pub fn render_rows(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        view: &View,
+        rows: Range<usize>,
+        histogram: &Histogram,
+    ) -> Vec<u8> {
+        // Adaptive supersampling compares each pixel with its neighbors,
+        // so it needs to see the rows on either side too.
+        let context = if self.supersampling.adaptive {
+            rows.start.saturating_sub(1)..(rows.end + 1).min(view.bounds.1)
+        } else {
+            rows.clone()
+        };
+
+        let samples = view.sample_rows(strategy, self.smooth, context.clone());
+        let pixels = self.finish(strategy, view, context.clone(), samples, histogram);
+        let row_bytes = 3 * view.bounds.0;
+
+        pixels[(rows.start - context.start) * row_bytes..][..rows.len() * row_bytes].to_vec()
+    }
+
+    /// Count `view`'s samples for `render_rows`, `band` rows at a time.
+    /// Without equalization nothing needs counting, so the counts are left
+    /// at zero.
+    pub fn histogram(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        view: &View,
+        band: usize,
+    ) -> Histogram {
+        let mut histogram = Histogram::new(view.limit);
+
+        if self.equalize {
+            for top in (0..view.bounds.1).step_by(band) {
+                let rows = top..(top + band).min(view.bounds.1);
+                histogram.add(&view.sample_rows(strategy, self.smooth, rows));
+            }
+        }
+
+        histogram
+    }
+
+    /// Color `samples`, one for each pixel in `rows`, and supersample the
+    /// pixels that need it.
+    fn finish(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        view: &View,
+        rows: Range<usize>,
+        samples: Vec<f32>,
+        histogram: &Histogram,
+    ) -> Vec<u8> {
+        let shade = self.shade(histogram);
+        let mut pixels = samples
+            .iter()
+            .flat_map(|&sample| shade(sample))
+            .collect::<Vec<_>>();
+
+        let supersampling = &self.supersampling;
+
+        if supersampling.grid > 1 {
+            let width = view.bounds.0;
+            let refine = supersampling.refine(&pixels, (width, rows.len()));
+            let per_pixel = supersampling.per_pixel();
+            let points = view.sample_within(
+                strategy,
+                self.smooth,
+                rows.clone(),
+                per_pixel,
+                &|column, row, index| {
+                    refine[(row - rows.start) * width + column]
+                        .then(|| supersampling.position(column, row, index))
+                },
+            );
+
+            for (i, pixel) in pixels.chunks_mut(3).enumerate() {
+                if !refine[i] {
+                    continue;
+                }
+
+                let mut sum = [0.0; 3];
+
+                for &point in &points[i * per_pixel..][..per_pixel] {
+                    let color = shade(point);
+
+                    for channel in 0..3 {
+                        sum[channel] += color[channel] as f64;
+                    }
+                }
+
+                for channel in 0..3 {
+                    pixel[channel] = (sum[channel] / per_pixel as f64).round() as u8;
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// Render `view` and write it to the PNG file `filename`: in grayscale
+    /// if the palette only has shades of gray, and in color otherwise.
+    /// Images too big to hold at once are written a band at a time, as
+    /// `write_in_bands` does.
+    pub fn write(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        view: &View,
+        filename: &str,
+    ) -> ImageResult<()> {
+        let band = (BAND_POINTS / (view.bounds.0 * self.supersampling.per_pixel()).max(1)).max(1);
+
+        if band < view.bounds.1 {
+            return self.write_in_bands(strategy, view, filename, band);
+        }
+
+        let pixels = self.render(strategy, view);
+
+        if self.palette.is_gray() {
+            write_image(filename, &gray(&pixels), view.bounds)
+        } else {
+            write_rgb_image(filename, &pixels, view.bounds)
+        }
+    }
+
+    /// Like `write`, but only rendering `band` rows at a time, and handing
+    /// each band to the PNG encoder before rendering the next. Memory use
+    /// then depends on the image's width, not its height. With histogram
+    /// equalization, the whole image is sampled twice: once to count the
+    /// samples, and once to color them.
+    pub fn write_in_bands(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        view: &View,
+        filename: &str,
+        band: usize,
+    ) -> ImageResult<()> {
+        let histogram = self.histogram(strategy, view, band);
+        let is_gray = self.palette.is_gray();
+
+        let bands = (0..view.bounds.1).step_by(band).map(|top| {
+            let rows = top..(top + band).min(view.bounds.1);
+            let pixels = self.render_rows(strategy, view, rows, &histogram);
+
+            if is_gray {
+                gray(&pixels)
+            } else {
+                pixels
+            }
+        });
+
+        let color_type = if is_gray {
+            ExtendedColorType::L8
+        } else {
+            ExtendedColorType::Rgb8
+        };
+
+        write_image_bands(filename, view.bounds, color_type, bands)
+    }
+
+    /// Color `samples`, which were taken with an iteration limit of `limit`.
+    pub fn colorize(&self, samples: &[f32], limit: usize) -> Vec<u8> {
+        let mut histogram = Histogram::new(limit);
+        histogram.add(samples);
+
+        let shade = self.shade(&histogram);
+
+        samples.iter().flat_map(|&sample| shade(sample)).collect()
+    }
+
+    /// The function that colors each sample, for an image whose samples
+    /// were counted in `histogram`.
+    fn shade(&self, histogram: &Histogram) -> impl Fn(f32) -> [u8; 3] + '_ {
+        let limit = histogram.counts.len() - 1;
+        let position: Box<dyn Fn(f32) -> f64> = if self.equalize {
+            let cdf = Cdf::new(histogram);
+            Box::new(move |sample| cdf.at(sample))
+        } else {
+            Box::new(move |sample| sample as f64 / limit as f64)
+        };
+
+        move |sample| {
+            if sample.is_nan() {
+                [0, 0, 0]
+            } else {
+                self.palette.color(position(sample))
+            }
+        }
+    }
+}
+
+/// One byte per pixel out of red, green and blue `pixels` that are all
+/// shades of gray.
+fn gray(pixels: &[u8]) -> Vec<u8> {
+    pixels.iter().step_by(3).copied().collect()
+}
+
+/// How many of an image's samples escaped after each whole number of
+/// iterations, which is all histogram equalization needs to know about it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// No samples yet, taken with an iteration limit of `limit`.
+    pub fn new(limit: usize) -> Histogram {
+        Histogram {
+            counts: vec![0; limit + 1],
+        }
+    }
+
+    /// Count `samples` too. Points in the set aren't counted.
+    pub fn add(&mut self, samples: &[f32]) {
+        let limit = self.counts.len() - 1;
+
+        for &sample in samples.iter().filter(|sample| !sample.is_nan()) {
+            self.counts[(sample as usize).min(limit)] += 1;
+        }
+    }
+}
+
+/// The iteration count at which a point escaped, made continuous: it goes
+/// from `count + 1`, when `z` only just left the circle of radius two, down
+/// to `count`, when `z` got so far out that it nearly left an iteration
+/// sooner. Neighboring pixels then get nearby values, instead of ones
+/// that jump by a whole iteration at the edge of each band.
+///
+/// `degree` is the power the fractal raises `z` to on each iteration.
+pub fn smooth_count(count: usize, norm: f64, degree: u32) -> f64 {
+    (count as f64 + 1.0 - (norm.ln() / LN_2).ln() / (degree as f64).ln()).max(0.0)
+}
+
+/// The sample to record for a point, given what `escape_time` said
+/// about it. Points in the set get NaN.
+pub fn sample(escape: Option<(usize, f64)>, smooth: bool, degree: u32) -> f32 {
+    match escape {
+        Some((count, norm)) if smooth => smooth_count(count, norm, degree) as f32,
+        Some((count, _)) => count as f32,
+        None => f32::NAN,
+    }
+}
+
+/// The cumulative distribution of the samples that escaped: for each
+/// whole iteration count, the fraction of them that escaped sooner.
+struct Cdf {
+    below: Vec<f64>,
+    within: Vec<f64>,
+}
+
+impl Cdf {
+    fn new(histogram: &Histogram) -> Cdf {
+        let total = histogram.counts.iter().sum::<usize>().max(1) as f64;
+        let within = histogram
+            .counts
+            .iter()
+            .map(|&count| count as f64 / total)
+            .collect::<Vec<_>>();
+        let below = within
+            .iter()
+            .scan(0.0, |sum, fraction| {
+                let below = *sum;
+                *sum += fraction;
+                Some(below)
+            })
+            .collect();
+
+        Cdf { below, within }
+    }
+
+    /// Where `sample` falls in the distribution, from 0 to 1. Fractional
+    /// samples are placed proportionally within their whole count's share.
+    fn at(&self, sample: f32) -> f64 {
+        let bin = (sample as usize).min(self.within.len() - 1);
+        let fraction = (sample as f64 - bin as f64).clamp(0.0, 1.0);
+
+        self.below[bin] + fraction * self.within[bin]
+    }
+}
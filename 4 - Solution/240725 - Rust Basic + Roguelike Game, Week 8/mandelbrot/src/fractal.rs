@@ -0,0 +1,181 @@
+//! The families of fractals we can draw. Each is a rule for iterating
+//! `z`, and a point belongs to the fractal if `z` never escapes the
+//! circle of radius two.
+
+use num::Complex;
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::perturbation::{Decimal, Orbit};
+use crate::simd::{self, LANES};
+use crate::{escape_time, parse_complex};
+
+/// The forms accepted by [`parse`].
+pub const FRACTALS: [&str; 5] = [
+    "mandelbrot",
+    "julia:RE,IM",
+    "multibrot:DEGREE",
+    "burning-ship",
+    "tricorn",
+];
+
+pub trait Fractal: Debug + Send + Sync {
+    /// Like the crate's `escape_time`, for the pixel at `point`.
+    fn escape_time(&self, point: Complex<f64>, limit: usize) -> Option<(usize, f64)>;
+
+    /// `escape_time` for `LANES` points at once. Families with a
+    /// vectorized kernel override this; the rest take the points one by one.
+    fn escape_times(
+        &self,
+        points: [Complex<f64>; LANES],
+        limit: usize,
+    ) -> [Option<(usize, f64)>; LANES] {
+        points.map(|point| self.escape_time(point, limit))
+    }
+
+    /// The power `z` is raised to on each iteration, which decides how
+    /// quickly it runs off once it has escaped.
+    fn degree(&self) -> u32 {
+        2
+    }
+
+    /// The orbit of `center`, for drawing deep zooms by perturbation, if
+    /// this family supports that. Those that don't are drawn in `f64`,
+    /// however deep the zoom.
+    fn reference_orbit(
+        &self,
+        _center: &Complex<Decimal>,
+        _bits: u32,
+        _limit: usize,
+    ) -> Option<Orbit> {
+        None
+    }
+}
+
+/// `z = z² + c`, starting from zero, with `c` the pixel's point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mandelbrot;
+
+impl Fractal for Mandelbrot {
+    fn escape_time(&self, point: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+        escape_time(point, limit)
+    }
+
+    fn escape_times(
+        &self,
+        points: [Complex<f64>; LANES],
+        limit: usize,
+    ) -> [Option<(usize, f64)>; LANES] {
+        simd::escape_times(points, limit)
+    }
+
+    fn reference_orbit(&self, center: &Complex<Decimal>, bits: u32, limit: usize) -> Option<Orbit> {
+        Some(Orbit::new(center, bits, limit))
+    }
+}
+
+/// `z = z² + c`, starting from the pixel's point, with `c` fixed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Julia {
+    pub c: Complex<f64>,
+}
+
+impl Fractal for Julia {
+    fn escape_time(&self, point: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+        iterate(point, limit, |z| z * z + self.c)
+    }
+}
+
+/// `z = z^degree + c`: the Mandelbrot set with a higher power.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Multibrot {
+    pub degree: u32,
+}
+
+impl Fractal for Multibrot {
+    fn escape_time(&self, c: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+        iterate(Complex { re: 0.0, im: 0.0 }, limit, |z| {
+            z.powu(self.degree) + c
+        })
+    }
+
+    fn degree(&self) -> u32 {
+        self.degree
+    }
+}
+
+/// `z = (|re z| + i |im z|)² + c`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BurningShip;
+
+impl Fractal for BurningShip {
+    fn escape_time(&self, c: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+        iterate(Complex { re: 0.0, im: 0.0 }, limit, |z| {
+            let folded = Complex {
+                re: z.re.abs(),
+                im: z.im.abs(),
+            };
+
+            folded * folded + c
+        })
+    }
+}
+
+/// `z = conj(z)² + c`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tricorn;
+
+impl Fractal for Tricorn {
+    fn escape_time(&self, c: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+        iterate(Complex { re: 0.0, im: 0.0 }, limit, |z| {
+            z.conj() * z.conj() + c
+        })
+    }
+}
+
+/// Apply `step` to `z` until it leaves the circle of radius two, as
+/// `escape_time` does.
+fn iterate(
+    mut z: Complex<f64>,
+    limit: usize,
+    step: impl Fn(Complex<f64>) -> Complex<f64>,
+) -> Option<(usize, f64)> {
+    for i in 0..limit {
+        if z.norm_sqr() > 4.0 {
+            return Some((i, z.norm()));
+        }
+
+        z = step(z);
+    }
+
+    None
+}
+
+/// Parse one of the forms in [`FRACTALS`], such as `julia:-0.8,0.156`.
+pub fn parse(s: &str) -> Result<Arc<dyn Fractal>, String> {
+    let (name, parameter) = match s.split_once(':') {
+        Some((name, parameter)) => (name, Some(parameter)),
+        None => (s, None),
+    };
+
+    match (name, parameter) {
+        ("mandelbrot", None) => Ok(Arc::new(Mandelbrot)),
+        ("julia", Some(c)) => match parse_complex(c) {
+            Some(c) => Ok(Arc::new(Julia { c })),
+            None => Err(format!("invalid Julia set constant {c:?}")),
+        },
+        ("multibrot", Some(degree)) => match degree.parse() {
+            Ok(degree) if degree >= 2 => Ok(Arc::new(Multibrot { degree })),
+            _ => Err(format!(
+                "invalid Multibrot degree {degree:?}; it must be 2 or more"
+            )),
+        },
+        ("burning-ship", None) => Ok(Arc::new(BurningShip)),
+        ("tricorn", None) => Ok(Arc::new(Tricorn)),
+        _ => Err(format!(
+            "unknown fractal {s:?}; choose one of: {}",
+            FRACTALS.join(", ")
+        )),
+    }
+}
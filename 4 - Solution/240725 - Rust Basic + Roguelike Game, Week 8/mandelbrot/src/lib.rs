@@ -0,0 +1,272 @@
+//! Rendering the Mandelbrot set, shared by the `mandelbrot-*` exercises.
+//!
+//! The functions here compute the image; a [`RenderStrategy`] decides how
+//! the work is split between threads. Every strategy renders each pixel
+//! from the same coordinates, so they all produce identical images.
+
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder, ImageResult};
+use num::Complex;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+pub mod animation;
+pub mod atomic_chunks;
+pub mod cli;
+pub mod color;
+pub mod fractal;
+pub mod perturbation;
+pub mod simd;
+pub mod strategy;
+pub mod supersample;
+pub mod view;
+
+pub use strategy::RenderStrategy;
+
+/// Try to determine if `c` is in the Mandelbrot set,
+/// using at most `limit` iterations to decide.
+///
+/// If `c` is not a member, return `Some((i, norm))`, where `i` is the number
+/// of iterations it took for `c` to leave the circle of radius two centered
+/// on the origin, and `norm` is how far from the origin `z` had got by then.
+/// If `c` seems to be a member (more precisely, if we reached the iteration
+/// limit without being able to prove that `c` is not a member), return `None`.
+pub fn escape_time(c: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+
+    for i in 0..limit {
+        if z.norm_sqr() > 4.0 {
+            return Some((i, z.norm()));
+        }
+
+        z = z * z + c;
+    }
+
+    None
+}
+
+/// Parse the string `s` as a coordinate pair, like `"400x600"` or `"1.0,0.5"`.
+///
+/// Specifically, `s` should have the form <left><sep><right>, where <sep> is
+/// the character given by the `separator` argument, and <left> and <right> are
+/// both strings that can be parsed by `T::from_str`.
+///
+/// If `s` has the proper form, return `Some<(x, y)>`.
+/// If it doesn't parse correctly, return `None`.
+pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
+    match s.find(separator) {
+        Some(index) => match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
+            (Ok(left), Ok(right)) => Some((left, right)),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+/// Parse a pair of floating-point numbers separated by a comma as a complex number.
+pub fn parse_complex(s: &str) -> Option<Complex<f64>> {
+    parse_pair(s, ',').map(|(re, im)| Complex { re, im })
+}
+
+/// Given the row and column of a pixel in the output image,
+/// return the corresponding point on the complex plane.
+///
+/// `bounds` is a pair giving the width and height of the image in pixels.
+/// `pixel` is a (column, row) pair indicating a particular pixel in that image.
+/// The `upper_left` and `lower_right` parameters are points on the complex plane
+/// designating the area our image covers.
+pub fn pixel_to_point(
+    bounds: (usize, usize),
+    pixel: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Complex<f64> {
+    subpixel_to_point(
+        bounds,
+        (pixel.0 as f64, pixel.1 as f64),
+        upper_left,
+        lower_right,
+    )
+}
+
+/// Like `pixel_to_point`, for a position anywhere within the image, such
+/// as `(10.5, 3.25)`: halfway across the pixel at column 10 and a quarter
+/// of the way down row 3.
+pub fn subpixel_to_point(
+    bounds: (usize, usize),
+    pixel: (f64, f64),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Complex<f64> {
+    let (width, height) = (
+        lower_right.re - upper_left.re,
+        upper_left.im - lower_right.im,
+    );
+
+    // Why subtraction here? pixel.1 increases as we go down,
+    // but the imaginary component increases as we go up.
+    Complex {
+        re: upper_left.re + pixel.0 * width / bounds.0 as f64,
+        im: upper_left.im - pixel.1 * height / bounds.1 as f64,
+    }
+}
+
+/// Render a rectangle of the Mandelbrot set into a buffer of pixels.
+///
+/// The `bounds` argument gives the width and height of the buffer `pixels`,
+/// which holds one grayscale pixel per byte. The `upper_left` and `lower_right`
+/// arguments specify points on the complex plane corresponding to the upper-left
+/// and lower-right corners of the pixel buffer. Each point gets at most `limit`
+/// iterations; the sooner it escapes, the lighter its pixel.
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+) {
+    assert_eq!(pixels.len(), bounds.0 * bounds.1);
+
+    render_rows(pixels, bounds, 0, upper_left, lower_right, limit);
+}
+
+/// Render some whole rows of the image described by `bounds`, `upper_left`
+/// and `lower_right` into `band`, starting with row `top`.
+///
+/// Each pixel's point is worked out from the whole image's corners, not the
+/// band's, so an image comes out the same however it is cut into bands.
+pub fn render_rows(
+    band: &mut [u8],
+    bounds: (usize, usize),
+    top: usize,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+) {
+    assert_eq!(band.len() % bounds.0, 0);
+
+    for (offset, row) in band.chunks_mut(bounds.0).enumerate() {
+        for (column, pixel) in row.iter_mut().enumerate() {
+            let point = pixel_to_point(bounds, (column, top + offset), upper_left, lower_right);
+
+            *pixel = match escape_time(point, limit) {
+                // Spread the counts over the grays, which with the usual
+                // limit of 255 is one shade per iteration.
+                Some((count, _)) => 255 - (count * 255 / limit) as u8,
+                None => 0,
+            };
+        }
+    }
+}
+
+/// Render the image described by `bounds`, `upper_left`, `lower_right`
+/// and `limit` into `pixels`, sharing the work out as `strategy` sees fit.
+pub fn render_with(
+    strategy: &dyn RenderStrategy,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    limit: usize,
+) {
+    assert_eq!(pixels.len(), bounds.0 * bounds.1);
+
+    strategy.render(pixels, bounds.0, &|top, band| {
+        render_rows(band, bounds, top, upper_left, lower_right, limit);
+    });
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds`,
+/// to the file named `filename`.
+pub fn write_image(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> ImageResult<()> {
+    write_png(filename, pixels, bounds, ExtendedColorType::L8)
+}
+
+/// Like `write_image`, but for a buffer holding three bytes per pixel:
+/// red, green and blue.
+pub fn write_rgb_image(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> ImageResult<()> {
+    write_png(filename, pixels, bounds, ExtendedColorType::Rgb8)
+}
+
+fn write_png(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    color_type: ExtendedColorType,
+) -> ImageResult<()> {
+    let output = File::create(filename)?;
+    let encoder = PngEncoder::new(output);
+
+    encoder.write_image(pixels, bounds.0 as u32, bounds.1 as u32, color_type)
+}
+
+/// Write a PNG file a band of rows at a time, as `bands` produces them,
+/// so the whole image never has to be in memory at once. `color_type` is
+/// `L8` for one byte per pixel, or `Rgb8` for three.
+pub fn write_image_bands(
+    filename: &str,
+    bounds: (usize, usize),
+    color_type: ExtendedColorType,
+    bands: impl IntoIterator<Item = Vec<u8>>,
+) -> ImageResult<()> {
+    let output = BufWriter::new(File::create(filename)?);
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+
+    encoder.set_color(match color_type {
+        ExtendedColorType::L8 => png::ColorType::Grayscale,
+        _ => png::ColorType::Rgb,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    let mut stream = writer.stream_writer().map_err(io::Error::from)?;
+
+    for band in bands {
+        stream.write_all(&band)?;
+    }
+
+    stream.finish().map_err(io::Error::from)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_pair() {
+    assert_eq!(parse_pair::<i32>("", ','), None);
+    assert_eq!(parse_pair::<i32>("10,", ','), None);
+    assert_eq!(parse_pair::<i32>(",10", ','), None);
+    assert_eq!(parse_pair::<i32>("10,20", ','), Some((10, 20)));
+    assert_eq!(parse_pair::<i32>("10,20xy", ','), None);
+    assert_eq!(parse_pair::<f64>("0.5x", 'x'), None);
+    assert_eq!(parse_pair::<f64>("0.5x1.5", 'x'), Some((0.5, 1.5)));
+}
+
+#[test]
+fn test_parse_complex() {
+    assert_eq!(
+        parse_complex("1.25,-0.0625"),
+        Some(Complex {
+            re: 1.25,
+            im: -0.0625
+        })
+    );
+    assert_eq!(parse_complex(",-0.0625"), None);
+}
+
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(
+        pixel_to_point(
+            (100, 200),
+            (25, 175),
+            Complex { re: -1.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 }
+        ),
+        Complex {
+            re: -0.5,
+            im: -0.75
+        }
+    );
+}
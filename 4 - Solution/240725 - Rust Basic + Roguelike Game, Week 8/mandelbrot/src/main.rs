@@ -0,0 +1,18 @@
+use mandelbrot::cli::{self, Usage};
+
+use std::env;
+
+fn main() {
+    let mut args = env::args().collect::<Vec<_>>();
+    let program = args.remove(0);
+    let usage = Usage::image(&format!("[--strategy NAME] [--threads N] {}", cli::OPTIONS));
+
+    let options = cli::take_options(&program, &mut args, &usage);
+    let strategy = cli::take_strategy(&program, &mut args, &usage);
+
+    if args.len() != 4 || args.iter().any(|arg| arg.starts_with("--")) {
+        usage.exit(&program);
+    }
+
+    cli::render_to_file(strategy.as_ref(), &options, &args);
+}
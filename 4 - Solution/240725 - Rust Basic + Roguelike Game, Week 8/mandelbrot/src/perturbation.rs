@@ -0,0 +1,257 @@
+//! Deep zooms by perturbation.
+//!
+//! Past a width of about 1e-13, neighboring pixels' points are so close
+//! together that `f64` can't tell them apart. Instead of iterating every
+//! pixel in arbitrary precision, which would be far too slow, we iterate
+//! one reference point, the center of the image, in fixed point with as
+//! many bits as the zoom needs. Each pixel then only tracks how far its
+//! own orbit is from the reference's, which is small enough for `f64`:
+//!
+//! ```text
+//! z = Z + dz,  c = C + dc
+//! dz' = 2·Z·dz + dz² + dc
+//! ```
+//!
+//! When `z` comes closer to zero than `dz` is, or the reference orbit
+//! runs out, the pixel's orbit is rebased onto the start of the reference
+//! orbit, which is what keeps the deltas accurate.
+
+use num::bigint::Sign;
+use num::{BigInt, Complex, ToPrimitive, Zero};
+
+use std::cmp::Ordering;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+/// A number parsed from decimal notation without losing any digits:
+/// `mantissa * 10^exponent`.
+#[derive(Clone, Debug)]
+pub struct Decimal {
+    mantissa: BigInt,
+    exponent: i64,
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+
+    /// Accepts the same forms as `f64`'s `from_str`, such as `-1.25`,
+    /// `.5` or `3e-20`, except for infinities and NaN.
+    fn from_str(s: &str) -> Result<Decimal, String> {
+        let error = || format!("invalid decimal number {s:?}");
+
+        let (number, exponent): (_, i64) = match s.find(['e', 'E']) {
+            Some(index) => (&s[..index], s[index + 1..].parse().map_err(|_| error())?),
+            None => (s, 0),
+        };
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let exponent = exponent
+            .checked_sub(fraction.len() as i64)
+            .ok_or_else(error)?;
+        let digits = format!("{whole}{fraction}");
+
+        if whole.trim_start_matches(['+', '-']).is_empty() && fraction.is_empty()
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(error());
+        }
+
+        // Nothing this far out is any use for drawing the set, and it
+        // would take a long time to convert to fixed point.
+        if !(-10_000..=10_000).contains(&exponent) {
+            return Err(error());
+        }
+
+        Ok(Decimal {
+            mantissa: digits.parse().map_err(|_| error())?,
+            exponent,
+        })
+    }
+}
+
+impl Decimal {
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.mantissa, self.exponent)
+            .parse()
+            .unwrap()
+    }
+
+    /// The number halfway between this one and `other`, exactly.
+    pub fn midpoint(&self, other: &Decimal) -> Decimal {
+        let sum = self + other;
+
+        // Halving a decimal: multiply by five and move the point one place.
+        Decimal {
+            mantissa: sum.mantissa * 5,
+            exponent: sum.exponent - 1,
+        }
+    }
+
+    /// This number times `factor`, exactly.
+    pub fn scale(&self, factor: f64) -> Decimal {
+        let factor = Decimal::from(factor);
+
+        Decimal {
+            mantissa: &self.mantissa * factor.mantissa,
+            exponent: self.exponent + factor.exponent,
+        }
+    }
+
+    /// The mantissa this number would have with the smaller `exponent`.
+    fn mantissa_at(&self, exponent: i64) -> BigInt {
+        &self.mantissa * BigInt::from(10).pow((self.exponent - exponent) as u32)
+    }
+
+    /// This number in fixed point with `bits` fractional bits, rounded
+    /// toward zero.
+    fn to_fixed(&self, bits: u32) -> BigInt {
+        let power = BigInt::from(10).pow(self.exponent.unsigned_abs() as u32);
+
+        if self.exponent >= 0 {
+            (&self.mantissa * power) << bits
+        } else {
+            (&self.mantissa << bits) / power
+        }
+    }
+}
+
+/// Decimals are equal if they have the same value, however many trailing
+/// zeros they were written with.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        (self - other).mantissa.is_zero()
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        (self - other).mantissa.sign().cmp(&Sign::NoSign)
+    }
+}
+
+impl Add for &Decimal {
+    type Output = Decimal;
+
+    fn add(self, other: &Decimal) -> Decimal {
+        let exponent = self.exponent.min(other.exponent);
+
+        Decimal {
+            mantissa: self.mantissa_at(exponent) + other.mantissa_at(exponent),
+            exponent,
+        }
+    }
+}
+
+impl Sub for &Decimal {
+    type Output = Decimal;
+
+    fn sub(self, other: &Decimal) -> Decimal {
+        let exponent = self.exponent.min(other.exponent);
+
+        Decimal {
+            mantissa: self.mantissa_at(exponent) - other.mantissa_at(exponent),
+            exponent,
+        }
+    }
+}
+
+impl From<f64> for Decimal {
+    /// The shortest decimal that reads back as `value`.
+    fn from(value: f64) -> Decimal {
+        format!("{value:e}").parse().unwrap()
+    }
+}
+
+/// Convert a fixed-point number with `bits` fractional bits to `f64`.
+fn fixed_to_f64(value: &BigInt, bits: u32) -> f64 {
+    // Keep only as many bits as an `f64` can use, so the conversion
+    // can't overflow however many the fixed point has.
+    let kept = bits.min(60);
+    let shifted = value >> (bits - kept);
+
+    shifted.to_f64().unwrap() / 2f64.powi(kept as i32)
+}
+
+/// The orbit of a reference point, from which the orbits of nearby
+/// points are worked out in `f64`.
+pub struct Orbit {
+    points: Vec<Complex<f64>>,
+}
+
+impl Orbit {
+    /// Iterate `center` with `bits` fractional bits of precision until it
+    /// escapes or has done `limit` iterations.
+    pub fn new(center: &Complex<Decimal>, bits: u32, limit: usize) -> Orbit {
+        let c = Complex {
+            re: center.re.to_fixed(bits),
+            im: center.im.to_fixed(bits),
+        };
+        let escape = BigInt::from(4) << (2 * bits);
+
+        let mut z = Complex {
+            re: BigInt::zero(),
+            im: BigInt::zero(),
+        };
+        let mut points = Vec::with_capacity(limit + 1);
+
+        for _ in 0..=limit {
+            points.push(Complex {
+                re: fixed_to_f64(&z.re, bits),
+                im: fixed_to_f64(&z.im, bits),
+            });
+
+            let (re2, im2) = (&z.re * &z.re, &z.im * &z.im);
+
+            if &re2 + &im2 > escape {
+                break;
+            }
+
+            let im = ((&z.re * &z.im) >> (bits - 1)) + &c.im;
+            z.re = ((re2 - im2) >> bits) + &c.re;
+            z.im = im;
+        }
+
+        Orbit { points }
+    }
+
+    /// Like `escape_time`, for the point `dc` away from the reference.
+    pub fn escape_time(&self, dc: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+        let mut dz = Complex { re: 0.0, im: 0.0 };
+        let mut m = 0;
+
+        for i in 0..limit {
+            let z = self.points[m] + dz;
+
+            if z.norm_sqr() > 4.0 {
+                return Some((i, z.norm()));
+            }
+
+            if z.norm_sqr() < dz.norm_sqr() || m == self.points.len() - 1 {
+                dz = z;
+                m = 0;
+            }
+
+            dz = dz * (self.points[m] * 2.0 + dz) + dc;
+            m += 1;
+        }
+
+        None
+    }
+}
+
+/// How many fractional bits the reference orbit needs, for pixels
+/// `spacing` apart.
+pub fn bits_for(spacing: f64) -> u32 {
+    // Enough to resolve a pixel, plus plenty to spare for the rounding
+    // errors that iterating piles up. No `f64` above zero is smaller than
+    // 2^-1074, so a spacing of zero, from a view too narrow for `f64`,
+    // gets as many bits as the narrowest one it can hold.
+    (-spacing.log2()).clamp(0.0, 1074.0) as u32 + 64
+}
@@ -0,0 +1,99 @@
+//! `escape_time` for several points at once.
+//!
+//! On x86-64 processors with AVX, four points are iterated together in
+//! one 256-bit register per coordinate. Each point that escapes is masked
+//! off and its result recorded, and the loop stops once they all have.
+//! Elsewhere, or without AVX, the points are iterated one at a time.
+//!
+//! Both paths do exactly the same floating-point operations, in the same
+//! order, as `escape_time`. In particular nothing is fused into a
+//! multiply-add, which would round differently, so the results are the
+//! same to the last bit.
+
+use num::Complex;
+
+use crate::escape_time;
+
+/// How many points `escape_times` takes at once.
+pub const LANES: usize = 4;
+
+/// What `escape_time` would return for each of `points`, worked out
+/// with vector instructions if the processor has them.
+pub fn escape_times(points: [Complex<f64>; LANES], limit: usize) -> [Option<(usize, f64)>; LANES] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx") {
+            // SAFETY: we just checked that the processor supports AVX.
+            return unsafe { x86_64::escape_times_avx(points, limit) };
+        }
+    }
+
+    escape_times_fallback(points, limit)
+}
+
+/// `escape_times` without vector instructions.
+pub fn escape_times_fallback(
+    points: [Complex<f64>; LANES],
+    limit: usize,
+) -> [Option<(usize, f64)>; LANES] {
+    points.map(|point| escape_time(point, limit))
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use num::Complex;
+
+    use std::arch::x86_64::*;
+
+    use super::LANES;
+
+    /// # Safety
+    ///
+    /// The processor must support AVX.
+    #[target_feature(enable = "avx")]
+    pub unsafe fn escape_times_avx(
+        points: [Complex<f64>; LANES],
+        limit: usize,
+    ) -> [Option<(usize, f64)>; LANES] {
+        let c_re = _mm256_setr_pd(points[0].re, points[1].re, points[2].re, points[3].re);
+        let c_im = _mm256_setr_pd(points[0].im, points[1].im, points[2].im, points[3].im);
+        let four = _mm256_set1_pd(4.0);
+
+        let mut z_re = _mm256_setzero_pd();
+        let mut z_im = _mm256_setzero_pd();
+        let mut results = [None; LANES];
+        // One bit per lane still iterating.
+        let mut active = (1 << LANES) - 1;
+
+        for i in 0..limit {
+            let re2 = _mm256_mul_pd(z_re, z_re);
+            let im2 = _mm256_mul_pd(z_im, z_im);
+            let norm_sqr = _mm256_add_pd(re2, im2);
+            let escaped = _mm256_movemask_pd(_mm256_cmp_pd::<_CMP_GT_OQ>(norm_sqr, four)) & active;
+
+            if escaped != 0 {
+                let (mut re, mut im) = ([0.0; LANES], [0.0; LANES]);
+                _mm256_storeu_pd(re.as_mut_ptr(), z_re);
+                _mm256_storeu_pd(im.as_mut_ptr(), z_im);
+
+                for lane in (0..LANES).filter(|lane| escaped & (1 << lane) != 0) {
+                    results[lane] = Some((i, re[lane].hypot(im[lane])));
+                }
+
+                active &= !escaped;
+
+                if active == 0 {
+                    break;
+                }
+            }
+
+            // z * z + c, as `Complex` works it out. Lanes that have escaped
+            // keep going, but nothing reads them any more.
+            let re_im = _mm256_mul_pd(z_re, z_im);
+            z_re = _mm256_add_pd(_mm256_sub_pd(re2, im2), c_re);
+            z_im = _mm256_add_pd(_mm256_add_pd(re_im, re_im), c_im);
+        }
+
+        results
+    }
+}
@@ -0,0 +1,184 @@
+//! Ways of sharing the rendering out between threads.
+//!
+//! A strategy cuts the pixel buffer into bands of whole rows and calls
+//! the renderer on each band, passing along the index of its top row.
+//! It doesn't look at the pixels, so the same strategy can fill a buffer
+//! of grayscale bytes or of escape samples.
+
+use rayon::prelude::*;
+
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use crate::atomic_chunks::AtomicChunksMut;
+
+/// Renders one band of rows: the index of its top row, and its pixels.
+pub type RenderRows<'a, P = u8> = dyn Fn(usize, &mut [P]) + Sync + 'a;
+
+pub trait RenderStrategy<P: Send = u8>: Sync {
+    /// The name used to pick this strategy on the command line.
+    fn name(&self) -> &str;
+
+    /// Render all of `pixels`, an image whose rows are `row_len` pixels
+    /// long, by calling `render_rows` on bands of it.
+    fn render(&self, pixels: &mut [P], row_len: usize, render_rows: &RenderRows<P>);
+}
+
+/// The names accepted by [`by_name`], in the order they were written.
+pub const NAMES: [&str; 5] = [
+    "single",
+    "thread-per-band",
+    "rayon",
+    "atomic-chunks",
+    "worker-pool",
+];
+
+/// Look up a strategy by name, giving the multithreaded ones `threads`
+/// threads to work with.
+pub fn by_name<P: Send>(name: &str, threads: usize) -> Option<Box<dyn RenderStrategy<P>>> {
+    let threads = threads.max(1);
+
+    match name {
+        "single" => Some(Box::new(Single)),
+        "thread-per-band" => Some(Box::new(ThreadPerBand { threads })),
+        "rayon" => Some(Box::new(Rayon)),
+        "atomic-chunks" => Some(Box::new(AtomicChunks { threads })),
+        "worker-pool" => Some(Box::new(WorkerPool { threads })),
+        _ => None,
+    }
+}
+
+/// Every strategy, for comparing them against one another.
+pub fn all<P: Send>(threads: usize) -> Vec<Box<dyn RenderStrategy<P>>> {
+    NAMES
+        .iter()
+        .map(|name| by_name(name, threads).unwrap())
+        .collect()
+}
+
+/// The whole image in one band, on the calling thread.
+pub struct Single;
+
+impl<P: Send> RenderStrategy<P> for Single {
+    fn name(&self) -> &str {
+        "single"
+    }
+
+    fn render(&self, pixels: &mut [P], _row_len: usize, render_rows: &RenderRows<P>) {
+        render_rows(0, pixels);
+    }
+}
+
+/// One band per thread, each as close to the same height as possible.
+pub struct ThreadPerBand {
+    pub threads: usize,
+}
+
+impl<P: Send> RenderStrategy<P> for ThreadPerBand {
+    fn name(&self) -> &str {
+        "thread-per-band"
+    }
+
+    fn render(&self, pixels: &mut [P], row_len: usize, render_rows: &RenderRows<P>) {
+        let height = pixels.len() / row_len;
+        let rows_per_band = height.div_ceil(self.threads).max(1);
+
+        thread::scope(|scope| {
+            for (i, band) in pixels.chunks_mut(rows_per_band * row_len).enumerate() {
+                scope.spawn(move || render_rows(i * rows_per_band, band));
+            }
+        });
+    }
+}
+
+/// One band per row, handed to rayon's thread pool.
+pub struct Rayon;
+
+impl<P: Send> RenderStrategy<P> for Rayon {
+    fn name(&self) -> &str {
+        "rayon"
+    }
+
+    fn render(&self, pixels: &mut [P], row_len: usize, render_rows: &RenderRows<P>) {
+        pixels
+            .par_chunks_mut(row_len)
+            .enumerate()
+            .for_each(|(top, band)| render_rows(top, band));
+    }
+}
+
+/// Many small bands, which each thread takes one at a time as it
+/// finishes the last, so no thread sits idle while another has a slow
+/// band left.
+pub struct AtomicChunks {
+    pub threads: usize,
+}
+
+impl<P: Send> RenderStrategy<P> for AtomicChunks {
+    fn name(&self) -> &str {
+        "atomic-chunks"
+    }
+
+    fn render(&self, pixels: &mut [P], row_len: usize, render_rows: &RenderRows<P>) {
+        let rows_per_band = pixels.len() / row_len / 400 + 1;
+        let bands = AtomicChunksMut::new(pixels, rows_per_band * row_len);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| {
+                    for (i, band) in &bands {
+                        render_rows(i * rows_per_band, band);
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// A fixed pool of threads, taking bands from a channel until it runs
+/// dry. Each band is its own `&mut` slice of the buffer, so the workers
+/// write their pixels in place.
+pub struct WorkerPool {
+    pub threads: usize,
+}
+
+impl<P: Send> RenderStrategy<P> for WorkerPool {
+    fn name(&self) -> &str {
+        "worker-pool"
+    }
+
+    fn render(&self, pixels: &mut [P], row_len: usize, render_rows: &RenderRows<P>) {
+        let height = pixels.len() / row_len;
+        // A few bands per thread, so one that drew an easy band can come
+        // back for another while the rest finish theirs.
+        let rows_per_band = height.div_ceil(self.threads * 4).max(1);
+
+        let (sender, receiver) = mpsc::channel();
+
+        for (i, band) in pixels.chunks_mut(rows_per_band * row_len).enumerate() {
+            sender.send((i * rows_per_band, band)).unwrap();
+        }
+
+        // Once the bands run out, `recv` fails instead of waiting for more.
+        drop(sender);
+
+        let receiver = Mutex::new(receiver);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    // Taken in a statement of its own, so the lock is
+                    // released before the band is drawn. In a `while let`
+                    // it would be held for the whole loop body.
+                    let next = receiver.lock().unwrap().recv();
+
+                    let Ok((top, band)) = next else {
+                        break;
+                    };
+
+                    render_rows(top, band);
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,115 @@
+//! Anti-aliasing by supersampling.
+//!
+//! One point per pixel decides the whole pixel's color, so the edges of
+//! the set come out jagged and thin filaments break up into dots.
+//! Supersampling takes a grid of points in each pixel and averages their
+//! colors instead. Adaptive supersampling first draws the image with one
+//! point per pixel, then only takes the grid where a pixel's color stands
+//! out from a neighbor's, which is where aliasing shows.
+
+/// How far apart two neighboring pixels' colors must be, in some channel,
+/// for adaptive supersampling to refine them.
+pub const THRESHOLD: u8 = 8;
+
+/// How many points to take in each pixel, and where.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Supersampling {
+    /// Points along each side of the pixel: 3 takes a 3x3 grid. With 1,
+    /// the only point is the pixel's upper left corner, as `pixel_to_point`
+    /// places it.
+    pub grid: usize,
+    /// Move each point to a random place within its cell of the grid,
+    /// which trades the grid's regular patterns for noise.
+    pub jitter: bool,
+    /// Only take the grid for pixels whose color differs from a
+    /// neighbor's.
+    pub adaptive: bool,
+}
+
+impl Default for Supersampling {
+    /// One point per pixel.
+    fn default() -> Supersampling {
+        Supersampling {
+            grid: 1,
+            jitter: false,
+            adaptive: false,
+        }
+    }
+}
+
+impl Supersampling {
+    pub fn per_pixel(&self) -> usize {
+        self.grid * self.grid
+    }
+
+    /// Where point `index` of the pixel at `column` and `row` is taken, as
+    /// a fraction of a pixel right and down from its upper left corner.
+    pub fn position(&self, column: usize, row: usize, index: usize) -> (f64, f64) {
+        let cell = ((index % self.grid) as f64, (index / self.grid) as f64);
+        let within = if self.jitter {
+            (
+                random(column, row, 2 * index),
+                random(column, row, 2 * index + 1),
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        (
+            (cell.0 + within.0) / self.grid as f64,
+            (cell.1 + within.1) / self.grid as f64,
+        )
+    }
+
+    /// Which of the pixels in `colors`, an image `bounds` in size drawn
+    /// with one point per pixel as red, green and blue bytes, to take the
+    /// grid for.
+    pub fn refine(&self, colors: &[u8], bounds: (usize, usize)) -> Vec<bool> {
+        let (width, height) = bounds;
+
+        if !self.adaptive {
+            return vec![true; width * height];
+        }
+
+        let color = |column: usize, row: usize| &colors[3 * (row * width + column)..][..3];
+        let differ = |a: &[u8], b: &[u8]| a.iter().zip(b).any(|(a, b)| a.abs_diff(*b) > THRESHOLD);
+
+        let mut refine = vec![false; width * height];
+
+        for row in 0..height {
+            for column in 0..width {
+                let here = color(column, row);
+
+                // Mark both sides of each edge, looking right and down.
+                if column + 1 < width && differ(here, color(column + 1, row)) {
+                    refine[row * width + column] = true;
+                    refine[row * width + column + 1] = true;
+                }
+                if row + 1 < height && differ(here, color(column, row + 1)) {
+                    refine[row * width + column] = true;
+                    refine[(row + 1) * width + column] = true;
+                }
+            }
+        }
+
+        refine
+    }
+}
+
+/// A number from 0 up to 1 that looks random, but is always the same for
+/// the same arguments. Every strategy then jitters every point the same
+/// way, whichever thread happens to draw it.
+fn random(column: usize, row: usize, index: usize) -> f64 {
+    // SplitMix64's finalizer, over the arguments packed into one number.
+    let mut x = (column as u64)
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add((row as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f))
+        .wrapping_add(index as u64);
+
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+
+    // The top 53 bits, which is as many as an `f64` holds.
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,195 @@
+//! What to draw: which part of the plane, at what size, and how hard to
+//! look for points escaping.
+
+use num::Complex;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::color;
+use crate::fractal::{Fractal, Mandelbrot};
+use crate::perturbation::{bits_for, Decimal};
+use crate::simd::LANES;
+use crate::{subpixel_to_point, RenderStrategy};
+
+/// Works out whether each of `LANES` points, given as positions in the
+/// image, escapes.
+type Escape<'a> = dyn Fn([(f64, f64); LANES]) -> [Option<(usize, f64)>; LANES] + Sync + 'a;
+
+/// How many iterations to try, unless the user asks for more or fewer.
+pub const DEFAULT_LIMIT: usize = 255;
+
+#[derive(Clone, Debug)]
+pub struct View {
+    pub fractal: Arc<dyn Fractal>,
+    /// The width and height of the image in pixels.
+    pub bounds: (usize, usize),
+    /// The corners of the image, to as many digits as they were given.
+    pub upper_left: Complex<Decimal>,
+    pub lower_right: Complex<Decimal>,
+    /// How many iterations to try before deciding a point is in the set.
+    pub limit: usize,
+}
+
+impl View {
+    pub fn new(
+        bounds: (usize, usize),
+        upper_left: Complex<f64>,
+        lower_right: Complex<f64>,
+    ) -> View {
+        View {
+            fractal: Arc::new(Mandelbrot),
+            bounds,
+            upper_left: Complex {
+                re: upper_left.re.into(),
+                im: upper_left.im.into(),
+            },
+            lower_right: Complex {
+                re: lower_right.re.into(),
+                im: lower_right.im.into(),
+            },
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    /// The width and height of the area the image covers.
+    pub fn size(&self) -> (f64, f64) {
+        (
+            (&self.lower_right.re - &self.upper_left.re).to_f64(),
+            (&self.upper_left.im - &self.lower_right.im).to_f64(),
+        )
+    }
+
+    /// Whether neighboring pixels are too close together for `f64` to
+    /// tell their points apart, so sampling should use perturbation.
+    pub fn is_deep(&self) -> bool {
+        let (width, height) = self.size();
+        let spacing = (width / self.bounds.0 as f64).min(height / self.bounds.1 as f64);
+        let magnitude = [
+            &self.upper_left.re,
+            &self.upper_left.im,
+            &self.lower_right.re,
+            &self.lower_right.im,
+        ]
+        .iter()
+        .map(|coordinate| coordinate.to_f64().abs())
+        .fold(1.0, f64::max);
+
+        // An `f64` has 52 bits of fraction; leave a dozen of them to
+        // resolve each pixel.
+        spacing < magnitude * 2f64.powi(-40)
+    }
+
+    /// Record a sample for every pixel, as `color::sample` describes,
+    /// sharing the work out as `strategy` sees fit.
+    pub fn sample(&self, strategy: &dyn RenderStrategy<f32>, smooth: bool) -> Vec<f32> {
+        self.sample_rows(strategy, smooth, 0..self.bounds.1)
+    }
+
+    /// Like `sample`, for only the pixels in `rows`.
+    pub fn sample_rows(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        smooth: bool,
+        rows: Range<usize>,
+    ) -> Vec<f32> {
+        self.sample_within(strategy, smooth, rows, 1, &|_, _, _| Some((0.0, 0.0)))
+    }
+
+    /// Record `per_pixel` samples for every pixel in `rows`, one after
+    /// another. Sample `index` of the pixel at `column` and `row` is taken
+    /// at the point `position(column, row, index)` returns, as a fraction
+    /// of a pixel right and down from its upper left corner; where that's
+    /// `None`, the sample is skipped and left as NaN.
+    pub fn sample_within(
+        &self,
+        strategy: &dyn RenderStrategy<f32>,
+        smooth: bool,
+        rows: Range<usize>,
+        per_pixel: usize,
+        position: &(dyn Fn(usize, usize, usize) -> Option<(f64, f64)> + Sync),
+    ) -> Vec<f32> {
+        let bounds = self.bounds;
+        let row_len = bounds.0 * per_pixel;
+        let mut samples = vec![f32::NAN; row_len * rows.len()];
+
+        let degree = self.fractal.degree();
+        let mut sample_rows = |escape: &Escape| {
+            strategy.render(&mut samples, row_len, &|top, band| {
+                for (offset, row_samples) in band.chunks_mut(row_len).enumerate() {
+                    let row = rows.start + top + offset;
+
+                    // The samples to take and where, gathered so they can
+                    // be worked out `LANES` at a time.
+                    let mut wanted = row_samples
+                        .iter_mut()
+                        .enumerate()
+                        .filter_map(|(i, sample)| {
+                            let (column, index) = (i / per_pixel, i % per_pixel);
+                            let (x, y) = position(column, row, index)?;
+
+                            Some((sample, (column as f64 + x, row as f64 + y)))
+                        })
+                        .collect::<Vec<_>>();
+
+                    for group in wanted.chunks_mut(LANES) {
+                        // A short last group is padded with its first point.
+                        let mut points = [group[0].1; LANES];
+
+                        for (point, (_, wanted)) in points.iter_mut().zip(group.iter()) {
+                            *point = *wanted;
+                        }
+
+                        for ((sample, _), escape) in group.iter_mut().zip(escape(points)) {
+                            **sample = color::sample(escape, smooth, degree);
+                        }
+                    }
+                }
+            });
+        };
+        let (width, height) = self.size();
+        let orbit = self.is_deep().then(|| {
+            let center = Complex {
+                re: self.upper_left.re.midpoint(&self.lower_right.re),
+                im: self.upper_left.im.midpoint(&self.lower_right.im),
+            };
+            let spacing = (width / bounds.0 as f64).min(height / bounds.1 as f64);
+
+            self.fractal
+                .reference_orbit(&center, bits_for(spacing), self.limit)
+        });
+
+        if let Some(orbit) = orbit.flatten() {
+            // Each point's offset from the center, placed on the plane as
+            // `pixel_to_point` would place it.
+            sample_rows(&|points| {
+                points.map(|(x, y)| {
+                    let dc = Complex {
+                        re: x * width / bounds.0 as f64 - width / 2.0,
+                        im: height / 2.0 - y * height / bounds.1 as f64,
+                    };
+
+                    orbit.escape_time(dc, self.limit)
+                })
+            });
+        } else {
+            let upper_left = Complex {
+                re: self.upper_left.re.to_f64(),
+                im: self.upper_left.im.to_f64(),
+            };
+            let lower_right = Complex {
+                re: self.lower_right.re.to_f64(),
+                im: self.lower_right.im.to_f64(),
+            };
+
+            sample_rows(&|points| {
+                let points =
+                    points.map(|(x, y)| subpixel_to_point(bounds, (x, y), upper_left, lower_right));
+
+                self.fractal.escape_times(points, self.limit)
+            });
+        }
+
+        samples
+    }
+}
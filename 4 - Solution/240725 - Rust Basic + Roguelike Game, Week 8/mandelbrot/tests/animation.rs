@@ -0,0 +1,135 @@
+use mandelbrot::animation::{frame_path, Flight};
+use mandelbrot::color::Coloring;
+use mandelbrot::strategy;
+use mandelbrot::view::View;
+use num::Complex;
+
+use std::fs;
+
+fn flight(frames: usize) -> Flight {
+    Flight {
+        start: View::new(
+            (16, 12),
+            Complex { re: -2.0, im: 1.5 },
+            Complex { re: 2.0, im: -1.5 },
+        ),
+        target: Complex {
+            re: "-0.75".parse().unwrap(),
+            im: "0.1".parse().unwrap(),
+        },
+        zoom: 1000.0,
+        frames,
+    }
+}
+
+fn corners(view: &View) -> [f64; 4] {
+    [
+        view.upper_left.re.to_f64(),
+        view.upper_left.im.to_f64(),
+        view.lower_right.re.to_f64(),
+        view.lower_right.im.to_f64(),
+    ]
+}
+
+fn assert_close(actual: [f64; 4], expected: [f64; 4]) {
+    for (actual, expected) in actual.into_iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{actual} is not close to {expected}"
+        );
+    }
+}
+
+#[test]
+fn test_first_frame_is_start() {
+    let flight = flight(10);
+
+    assert_close(corners(&flight.frame(0)), corners(&flight.start));
+}
+
+#[test]
+fn test_last_frame_is_target() {
+    let flight = flight(10);
+
+    // 4 by 3 at the start, a thousand times smaller at the end.
+    assert_close(corners(&flight.frame(9)), [-0.752, 0.1015, -0.748, 0.0985]);
+}
+
+#[test]
+fn test_zoom_is_exponential() {
+    let flight = flight(7);
+    let widths = (0..7)
+        .map(|index| flight.frame(index).size().0)
+        .collect::<Vec<_>>();
+
+    for pair in widths.windows(2) {
+        assert!((pair[0] / pair[1] - 10f64.sqrt()).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_render_resumes() {
+    let directory =
+        std::env::temp_dir().join(format!("mandelbrot-animation-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    let flight = flight(4);
+    let coloring = Coloring::default();
+
+    assert_eq!(
+        flight
+            .render(&strategy::Single, &coloring, &directory)
+            .unwrap(),
+        4
+    );
+
+    // Pretend the run was cut off after the first two frames.
+    fs::remove_file(frame_path(&directory, 2)).unwrap();
+    fs::remove_file(frame_path(&directory, 3)).unwrap();
+    let second = fs::read(frame_path(&directory, 1)).unwrap();
+    fs::write(frame_path(&directory, 1), "not rendered again").unwrap();
+
+    assert_eq!(
+        flight
+            .render(&strategy::Single, &coloring, &directory)
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        fs::read(frame_path(&directory, 1)).unwrap(),
+        b"not rendered again"
+    );
+    assert!(frame_path(&directory, 3).exists());
+
+    // Nothing left to do.
+    assert_eq!(
+        flight
+            .render(&strategy::Single, &coloring, &directory)
+            .unwrap(),
+        0
+    );
+
+    // A different flight, or the same one colored differently, must not
+    // carry on from these frames.
+    let elsewhere = Flight {
+        zoom: 10.0,
+        ..self::flight(4)
+    };
+    assert!(elsewhere
+        .render(&strategy::Single, &coloring, &directory)
+        .is_err());
+    let smooth = Coloring {
+        smooth: true,
+        ..Coloring::default()
+    };
+    assert!(flight
+        .render(&strategy::Single, &smooth, &directory)
+        .is_err());
+
+    fs::write(frame_path(&directory, 1), second).unwrap();
+    let gif = directory.join("zoom.gif");
+    flight.write_gif(&directory, &gif, 30).unwrap();
+    assert!(image::open(&gif).is_ok());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,74 @@
+//! Small enough to run under Miri, which checks that the chunks really
+//! are disjoint `&mut` borrows:
+//!
+//!     cargo +nightly miri test -p mandelbrot --test atomic_chunks
+#![cfg(not(loom))]
+
+use mandelbrot::atomic_chunks::AtomicChunksMut;
+
+use std::thread;
+
+#[test]
+fn test_chunks_cover_the_slice_in_order() {
+    let mut numbers = [0; 10];
+    let chunks = AtomicChunksMut::new(&mut numbers, 4);
+
+    let claimed = (&chunks)
+        .map(|(i, chunk)| (i, chunk.len()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(claimed, [(0, 4), (1, 4), (2, 2)]);
+    assert!(chunks.claim().is_none());
+}
+
+#[test]
+fn test_empty_slice_has_no_chunks() {
+    let chunks = AtomicChunksMut::new(&mut [] as &mut [u8], 3);
+
+    assert!(chunks.claim().is_none());
+}
+
+#[test]
+#[should_panic(expected = "chunk size must be non-zero")]
+fn test_zero_step_panics() {
+    AtomicChunksMut::new(&mut [1, 2, 3], 0);
+}
+
+#[test]
+fn test_threads_write_every_element_once() {
+    let mut numbers = [0usize; 23];
+
+    {
+        let chunks = AtomicChunksMut::new(&mut numbers, 3);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for (i, chunk) in &chunks {
+                        for (offset, number) in chunk.iter_mut().enumerate() {
+                            *number += i * 3 + offset + 1;
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    assert_eq!(numbers.to_vec(), (1..=23).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_chunks_outlive_the_dispenser() {
+    let mut numbers = [0; 6];
+
+    let mut chunks = {
+        let dispenser = AtomicChunksMut::new(&mut numbers, 2);
+        (&dispenser).map(|(_, chunk)| chunk).collect::<Vec<_>>()
+    };
+
+    for (i, chunk) in chunks.iter_mut().enumerate() {
+        chunk.fill(i);
+    }
+
+    assert_eq!(numbers, [0, 0, 1, 1, 2, 2]);
+}
@@ -0,0 +1,71 @@
+use mandelbrot::color::{Coloring, Palette};
+use mandelbrot::strategy;
+use mandelbrot::supersample::Supersampling;
+use mandelbrot::view::View;
+use num::Complex;
+
+use std::env;
+use std::fs;
+
+const BOUNDS: (usize, usize) = (40, 31);
+
+fn colorings() -> Vec<Coloring> {
+    let fire = Palette::named("fire").unwrap();
+
+    vec![
+        Coloring::default(),
+        Coloring {
+            palette: fire.clone(),
+            smooth: true,
+            equalize: true,
+            ..Coloring::default()
+        },
+        Coloring {
+            palette: fire,
+            smooth: true,
+            equalize: true,
+            supersampling: Supersampling {
+                grid: 2,
+                jitter: true,
+                adaptive: true,
+            },
+        },
+    ]
+}
+
+#[test]
+fn test_bands_match_the_whole_image() {
+    let view = View::new(
+        BOUNDS,
+        Complex {
+            re: -1.20,
+            im: 0.35,
+        },
+        Complex {
+            re: -1.00,
+            im: 0.20,
+        },
+    );
+    let path = env::temp_dir().join(format!("mandelbrot-bands-{}.png", std::process::id()));
+    let filename = path.to_str().unwrap();
+
+    for coloring in colorings() {
+        let expected = coloring.render(&strategy::Single, &view);
+
+        for band in [1, 7, 31, 100] {
+            coloring
+                .write_in_bands(&strategy::Rayon, &view, filename, band)
+                .unwrap();
+
+            let image = image::open(&path).unwrap().into_rgb8();
+
+            assert_eq!(image.dimensions(), (BOUNDS.0 as u32, BOUNDS.1 as u32));
+            assert!(
+                image.as_raw() == &expected,
+                "{coloring:?} differs in bands of {band} rows"
+            );
+        }
+    }
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,104 @@
+use mandelbrot::color::{smooth_count, Coloring, Palette};
+use mandelbrot::view::{View, DEFAULT_LIMIT};
+use mandelbrot::{escape_time, render, strategy};
+use num::Complex;
+
+const BOUNDS: (usize, usize) = (64, 48);
+
+fn corners() -> (Complex<f64>, Complex<f64>) {
+    (
+        Complex {
+            re: -1.20,
+            im: 0.35,
+        },
+        Complex {
+            re: -1.00,
+            im: 0.20,
+        },
+    )
+}
+
+#[test]
+fn test_default_coloring_matches_grayscale() {
+    let (upper_left, lower_right) = corners();
+
+    let mut expected = vec![0; BOUNDS.0 * BOUNDS.1];
+    render(
+        &mut expected,
+        BOUNDS,
+        upper_left,
+        lower_right,
+        DEFAULT_LIMIT,
+    );
+
+    let pixels = Coloring::default().render(
+        &strategy::Single,
+        &View::new(BOUNDS, upper_left, lower_right),
+    );
+
+    for (pixel, gray) in pixels.chunks(3).zip(expected) {
+        assert_eq!(pixel, [gray; 3]);
+    }
+}
+
+#[test]
+fn test_smooth_count_stays_near_the_whole_count() {
+    let (upper_left, lower_right) = corners();
+
+    for re in 0..50 {
+        let point = Complex {
+            re: upper_left.re + (lower_right.re - upper_left.re) * re as f64 / 50.0,
+            im: 0.3,
+        };
+
+        if let Some((count, norm)) = escape_time(point, DEFAULT_LIMIT) {
+            let smooth = smooth_count(count, norm, 2);
+
+            assert!(smooth > count as f64 - 1.0 && smooth <= count as f64 + 1.0);
+        }
+    }
+}
+
+#[test]
+fn test_equalization_uses_the_whole_palette() {
+    let (upper_left, lower_right) = corners();
+    let coloring = Coloring {
+        palette: Palette::new(vec![[0, 0, 0], [255, 0, 0]]),
+        smooth: true,
+        equalize: true,
+        ..Coloring::default()
+    };
+
+    let pixels = coloring.render(
+        &strategy::Single,
+        &View::new(BOUNDS, upper_left, lower_right),
+    );
+    let reds = pixels.iter().step_by(3).copied().collect::<Vec<_>>();
+
+    // Escaped pixels spread evenly from black to red, so about half of
+    // them should be on either side of the middle.
+    let escaped = reds.iter().filter(|&&red| red != 0).count();
+    let bright = reds.iter().filter(|&&red| red > 128).count();
+
+    assert!(escaped > 0);
+    assert!((bright as f64 / escaped as f64 - 0.5).abs() < 0.1);
+}
+
+#[test]
+fn test_palette_files() {
+    let palette = Palette::parse("# black to white\n0 0 0\n\n255 255 255\n").unwrap();
+
+    assert_eq!(palette, Palette::new(vec![[0, 0, 0], [255, 255, 255]]));
+    assert!(palette.is_gray());
+    assert_eq!(palette.color(0.5), [128, 128, 128]);
+
+    assert_eq!(
+        Palette::parse("0 0 0\n255 0\n"),
+        Err(String::from("line 2: expected three numbers from 0 to 255"))
+    );
+    assert_eq!(
+        Palette::parse("0 0 256\n"),
+        Err(String::from("line 1: expected three numbers from 0 to 255"))
+    );
+    assert!(Palette::parse("0 0 0\n").is_err());
+}
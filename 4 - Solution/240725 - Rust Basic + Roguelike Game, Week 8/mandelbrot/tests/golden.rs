@@ -0,0 +1,76 @@
+//! Each fractal family drawn small, and compared pixel for pixel with an
+//! image in `tests/golden`. After changing how a family is drawn on
+//! purpose, look over the new images written by
+//!
+//!     UPDATE_GOLDEN=1 cargo test -p mandelbrot --test golden
+//!
+//! and commit them along with the change.
+
+use mandelbrot::color::Coloring;
+use mandelbrot::view::View;
+use mandelbrot::{fractal, parse_complex, strategy, write_image};
+
+use std::env;
+use std::path::Path;
+
+const BOUNDS: (usize, usize) = (48, 36);
+
+fn check(name: &str, spec: &str, upper_left: &str, lower_right: &str) {
+    let mut view = View::new(
+        BOUNDS,
+        parse_complex(upper_left).unwrap(),
+        parse_complex(lower_right).unwrap(),
+    );
+    view.fractal = fractal::parse(spec).unwrap();
+
+    let pixels = Coloring::default()
+        .render(&strategy::Single, &view)
+        .into_iter()
+        .step_by(3)
+        .collect::<Vec<_>>();
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.png"));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        write_image(path.to_str().unwrap(), &pixels, BOUNDS).unwrap();
+        return;
+    }
+
+    let golden = image::open(&path)
+        .unwrap_or_else(|error| panic!("{}: {error}", path.display()))
+        .into_luma8();
+
+    assert_eq!(golden.dimensions(), (BOUNDS.0 as u32, BOUNDS.1 as u32));
+    assert!(
+        golden.as_raw() == &pixels,
+        "{spec} no longer matches {}",
+        path.display()
+    );
+}
+
+#[test]
+fn test_mandelbrot() {
+    check("mandelbrot", "mandelbrot", "-2.2,1.125", "0.8,-1.125");
+}
+
+#[test]
+fn test_julia() {
+    check("julia", "julia:-0.8,0.156", "-1.6,1.2", "1.6,-1.2");
+}
+
+#[test]
+fn test_multibrot() {
+    check("multibrot-3", "multibrot:3", "-1.6,1.2", "1.6,-1.2");
+}
+
+#[test]
+fn test_burning_ship() {
+    check("burning-ship", "burning-ship", "-2.5,1.0", "1.5,-2.0");
+}
+
+#[test]
+fn test_tricorn() {
+    check("tricorn", "tricorn", "-2.2,1.5", "1.8,-1.5");
+}
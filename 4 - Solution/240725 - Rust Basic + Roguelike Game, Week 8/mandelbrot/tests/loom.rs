@@ -0,0 +1,40 @@
+//! Checks every interleaving of threads claiming chunks. Run with:
+//!
+//!     RUSTFLAGS="--cfg loom" cargo test -p mandelbrot --test loom --release
+#![cfg(loom)]
+
+use loom::sync::Arc;
+use loom::thread;
+use mandelbrot::atomic_chunks::AtomicChunksMut;
+
+#[test]
+fn test_each_chunk_is_claimed_once() {
+    loom::model(|| {
+        // Threads spawned by loom must be 'static, so give it a slice that is.
+        let numbers = Box::leak(vec![0u8; 5].into_boxed_slice());
+        let chunks = Arc::new(AtomicChunksMut::new(numbers, 2));
+
+        let workers = (0..2)
+            .map(|_| {
+                let chunks = chunks.clone();
+
+                thread::spawn(move || {
+                    (&*chunks)
+                        .map(|(i, chunk)| (i, chunk.as_ptr() as usize, chunk.len()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut claimed = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>();
+        claimed.sort();
+
+        let start = claimed[0].1;
+        let expected = [(0, start, 2), (1, start + 2, 2), (2, start + 4, 1)];
+
+        assert_eq!(claimed, expected);
+    });
+}
@@ -0,0 +1,170 @@
+use mandelbrot::escape_time;
+use mandelbrot::fractal::Mandelbrot;
+use mandelbrot::perturbation::{bits_for, Decimal, Orbit};
+use mandelbrot::view::View;
+use mandelbrot::{pixel_to_point, strategy};
+use num::{Complex, Zero};
+
+use std::sync::Arc;
+
+fn decimal(s: &str) -> Decimal {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_decimals() {
+    assert_eq!(decimal("-1.25").to_f64(), -1.25);
+    assert_eq!(decimal(".5").to_f64(), 0.5);
+    assert_eq!(decimal("+3e-20").to_f64(), 3e-20);
+    assert_eq!(decimal("2.5E2"), decimal("250"));
+    assert_eq!(Decimal::from(-0.1), decimal("-0.1"));
+
+    assert_eq!(
+        decimal("-0.7436438870371587047721").midpoint(&decimal("-0.7436438870371587047321")),
+        decimal("-0.7436438870371587047521")
+    );
+    assert_eq!(
+        &decimal("1.00000000000000000000001") - &decimal("1"),
+        decimal("1e-23")
+    );
+    assert!(decimal("1.00000000000000000000001") > decimal("1"));
+    assert!(decimal("-2e-30") < decimal("0.0"));
+
+    for invalid in [
+        "",
+        ".",
+        "-",
+        "1.2.3",
+        "1,2",
+        "e5",
+        "1e",
+        "1e999999",
+        "nan",
+        "0e-9223372036854775808",
+        "0.5e-9223372036854775807",
+    ] {
+        assert!(invalid.parse::<Decimal>().is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_bits_for_spacings_too_small_for_f64() {
+    assert_eq!(bits_for(1.0), 64);
+    assert_eq!(bits_for(0.0), bits_for(f64::from_bits(1)));
+}
+
+#[test]
+fn test_perturbation_agrees_with_f64_at_shallow_zooms() {
+    let center = Complex {
+        re: decimal("-0.7436"),
+        im: decimal("0.1318"),
+    };
+    let c = Complex {
+        re: center.re.to_f64(),
+        im: center.im.to_f64(),
+    };
+    let orbit = Orbit::new(&center, bits_for(1e-6), 2000);
+
+    let mut agree = 0;
+
+    for i in 0..400 {
+        let dc = Complex {
+            re: (i % 20) as f64 * 1e-5 - 1e-4,
+            im: (i / 20) as f64 * 1e-5 - 1e-4,
+        };
+
+        if orbit.escape_time(dc, 2000).map(|(count, _)| count)
+            == escape_time(c + dc, 2000).map(|(count, _)| count)
+        {
+            agree += 1;
+        }
+    }
+
+    // Both round differently, which tips the odd point near the boundary.
+    assert!(agree >= 396, "only {agree} of 400 points agree");
+}
+
+#[test]
+fn test_perturbation_agrees_with_fixed_point_at_deep_zooms() {
+    let center = Complex {
+        re: decimal("-0.743643887037158704752191506114774"),
+        im: decimal("0.131825904205311970493132056385139"),
+    };
+    let bits = bits_for(1e-21);
+    let limit = 50_000;
+    let orbit = Orbit::new(&center, bits, limit);
+
+    for i in 0..25 {
+        let offset = ((i % 5) * 7 - 14, (i / 5) * 7 - 14);
+        let dc = Complex {
+            re: offset.0 as f64 * 1e-21,
+            im: offset.1 as f64 * 1e-21,
+        };
+
+        // A point's own orbit, iterated wholly in fixed point, is exact
+        // enough to check the perturbed one against.
+        let point = Complex {
+            re: &center.re - &decimal(&format!("{}e-21", -offset.0)),
+            im: &center.im - &decimal(&format!("{}e-21", -offset.1)),
+        };
+        let exact = Orbit::new(&point, bits, limit).escape_time(Complex::zero(), limit);
+
+        let perturbed = orbit.escape_time(dc, limit);
+
+        // After thousands of iterations, rounding can tip a point out a
+        // few iterations sooner or later, but no more than that.
+        match (perturbed, exact) {
+            (Some((perturbed, _)), Some((exact, _))) => {
+                assert!(
+                    perturbed.abs_diff(exact) <= exact / 1000,
+                    "{perturbed} vs {exact} at {offset:?}"
+                )
+            }
+            _ => assert_eq!(perturbed, exact, "at {offset:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_deep_zooms_resolve_every_pixel() {
+    let view = View {
+        fractal: Arc::new(Mandelbrot),
+        bounds: (32, 24),
+        upper_left: Complex {
+            re: decimal("-0.743643887037158704772191506114774"),
+            im: decimal("0.131825904205311970508132056385139"),
+        },
+        lower_right: Complex {
+            re: decimal("-0.743643887037158704732191506114774"),
+            im: decimal("0.131825904205311970478132056385139"),
+        },
+        limit: 50_000,
+    };
+
+    assert!(view.is_deep());
+
+    // In `f64`, whole blocks of these pixels land on the same point.
+    let corner = |c: &Complex<Decimal>| Complex {
+        re: c.re.to_f64(),
+        im: c.im.to_f64(),
+    };
+    let (upper_left, lower_right) = (corner(&view.upper_left), corner(&view.lower_right));
+    assert_eq!(
+        pixel_to_point(view.bounds, (0, 0), upper_left, lower_right),
+        pixel_to_point(view.bounds, (1, 0), upper_left, lower_right)
+    );
+
+    let samples = view.sample(&strategy::Single, false);
+    let mut distinct = samples
+        .iter()
+        .map(|sample| sample.to_bits())
+        .collect::<Vec<_>>();
+    distinct.sort();
+    distinct.dedup();
+
+    assert!(
+        distinct.len() > 100,
+        "only {} distinct samples",
+        distinct.len()
+    );
+}
@@ -0,0 +1,71 @@
+use mandelbrot::escape_time;
+use mandelbrot::simd::{escape_times, escape_times_fallback, LANES};
+use num::Complex;
+
+/// Points all over the set and around it, including some exactly on the
+/// circle of radius two and some that never escape.
+fn points() -> Vec<Complex<f64>> {
+    let mut points = vec![
+        Complex { re: 2.0, im: 0.0 },
+        Complex { re: 0.0, im: -2.0 },
+        Complex { re: -2.0, im: 0.0 },
+        Complex { re: 0.0, im: 0.0 },
+        Complex { re: -1.0, im: 0.0 },
+        Complex { re: 0.25, im: 0.0 },
+        Complex { re: -0.75, im: 0.1 },
+    ];
+
+    for row in 0..61 {
+        for column in 0..83 {
+            points.push(Complex {
+                re: -2.5 + column as f64 * 3.5 / 82.0,
+                im: 1.5 - row as f64 * 3.0 / 60.0,
+            });
+        }
+    }
+
+    points
+}
+
+fn bits(escape: Option<(usize, f64)>) -> Option<(usize, u64)> {
+    escape.map(|(count, norm)| (count, norm.to_bits()))
+}
+
+#[test]
+fn test_lanes_match_escape_time_bit_for_bit() {
+    let points = points();
+
+    for limit in [0, 1, 2, 255, 2000] {
+        for group in points.chunks_exact(LANES) {
+            let group: [Complex<f64>; LANES] = group.try_into().unwrap();
+            let expected = group.map(|point| bits(escape_time(point, limit)));
+
+            assert_eq!(escape_times(group, limit).map(bits), expected, "{group:?}");
+            assert_eq!(
+                escape_times_fallback(group, limit).map(bits),
+                expected,
+                "{group:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_lanes_are_independent() {
+    // One point that escapes at once alongside three that never do, in
+    // every position.
+    for lane in 0..LANES {
+        let mut group = [Complex { re: -0.1, im: 0.1 }; LANES];
+        group[lane] = Complex { re: 3.0, im: 0.0 };
+
+        let escapes = escape_times(group, 100);
+
+        for (other, escape) in escapes.into_iter().enumerate() {
+            if other == lane {
+                assert_eq!(escape.map(|(count, _)| count), Some(1));
+            } else {
+                assert_eq!(escape, None);
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use mandelbrot::view::View;
+use mandelbrot::{render, strategy};
+use num::Complex;
+
+/// Heights that divide evenly among the threads, and heights that don't.
+const BOUNDS: [(usize, usize); 4] = [(64, 48), (61, 47), (17, 1), (3, 801)];
+
+const UPPER_LEFT: Complex<f64> = Complex {
+    re: -1.20,
+    im: 0.35,
+};
+const LOWER_RIGHT: Complex<f64> = Complex {
+    re: -1.00,
+    im: 0.20,
+};
+
+/// Samples are NaN for points in the set, so compare their bits.
+fn bits(samples: Vec<f32>) -> Vec<u32> {
+    samples.into_iter().map(f32::to_bits).collect()
+}
+
+#[test]
+fn test_strategies_sample_identical_pixels() {
+    for bounds in BOUNDS {
+        for limit in [255, 1000] {
+            let view = View {
+                limit,
+                ..View::new(bounds, UPPER_LEFT, LOWER_RIGHT)
+            };
+            let expected = bits(view.sample(&strategy::Single, true));
+
+            for threads in [1, 3, 8] {
+                for strategy in strategy::all(threads) {
+                    assert!(
+                        bits(view.sample(strategy.as_ref(), true)) == expected,
+                        "{} with {threads} threads differs at {bounds:?}, limit {limit}",
+                        strategy.name()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render_honors_the_limit() {
+    let bounds = (64, 48);
+    let mut shallow = vec![0; bounds.0 * bounds.1];
+    let mut deep = vec![0; bounds.0 * bounds.1];
+
+    render(&mut shallow, bounds, UPPER_LEFT, LOWER_RIGHT, 255);
+    render(&mut deep, bounds, UPPER_LEFT, LOWER_RIGHT, 1000);
+
+    assert!(shallow != deep);
+}
+
+#[test]
+fn test_strategies_are_found_by_name() {
+    for name in strategy::NAMES {
+        assert_eq!(strategy::by_name::<u8>(name, 4).unwrap().name(), name);
+    }
+
+    assert!(strategy::by_name::<u8>("gpu", 4).is_none());
+}
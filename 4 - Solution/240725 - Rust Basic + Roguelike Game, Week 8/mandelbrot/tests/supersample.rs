@@ -0,0 +1,125 @@
+use mandelbrot::color::{Coloring, Palette};
+use mandelbrot::strategy;
+use mandelbrot::supersample::Supersampling;
+use mandelbrot::view::View;
+use num::Complex;
+
+fn view(bounds: (usize, usize)) -> View {
+    View::new(
+        bounds,
+        Complex {
+            re: -1.20,
+            im: 0.35,
+        },
+        Complex {
+            re: -1.00,
+            im: 0.20,
+        },
+    )
+}
+
+fn coloring(grid: usize, jitter: bool, adaptive: bool) -> Coloring {
+    Coloring {
+        palette: Palette::named("fire").unwrap(),
+        smooth: true,
+        supersampling: Supersampling {
+            grid,
+            jitter,
+            adaptive,
+        },
+        ..Coloring::default()
+    }
+}
+
+#[test]
+fn test_grid_positions() {
+    let grid = Supersampling {
+        grid: 2,
+        ..Supersampling::default()
+    };
+    let positions = (0..4)
+        .map(|index| grid.position(7, 3, index))
+        .collect::<Vec<_>>();
+
+    assert_eq!(positions, [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)]);
+    assert_eq!(Supersampling::default().position(7, 3, 0), (0.0, 0.0));
+}
+
+#[test]
+fn test_jittered_positions_stay_in_their_cells() {
+    let jittered = Supersampling {
+        grid: 3,
+        jitter: true,
+        adaptive: false,
+    };
+
+    for index in 0..9 {
+        let (x, y) = jittered.position(5, 11, index);
+        let cell = ((index % 3) as f64 / 3.0, (index / 3) as f64 / 3.0);
+
+        assert!(x >= cell.0 && x < cell.0 + 1.0 / 3.0);
+        assert!(y >= cell.1 && y < cell.1 + 1.0 / 3.0);
+        assert_eq!(jittered.position(5, 11, index), (x, y));
+    }
+
+    assert_ne!(jittered.position(5, 11, 0), jittered.position(6, 11, 0));
+}
+
+#[test]
+fn test_one_point_per_pixel_is_unchanged() {
+    let view = view((40, 30));
+    let coloring = coloring(1, false, false);
+
+    let samples = view.sample(&strategy::Single, coloring.smooth);
+
+    assert_eq!(
+        coloring.render(&strategy::Single, &view),
+        coloring.colorize(&samples, view.limit)
+    );
+}
+
+#[test]
+fn test_strategies_supersample_identical_pixels() {
+    for bounds in [(40, 30), (17, 1), (3, 201)] {
+        let view = view(bounds);
+
+        for (grid, jitter, adaptive) in [(2, false, false), (3, true, false), (3, true, true)] {
+            let coloring = coloring(grid, jitter, adaptive);
+            let expected = coloring.render(&strategy::Single, &view);
+
+            for threads in [1, 3, 8] {
+                for strategy in strategy::all(threads) {
+                    assert!(
+                        coloring.render(strategy.as_ref(), &view) == expected,
+                        "{} with {threads} threads differs at {bounds:?}",
+                        strategy.name()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_adaptive_only_refines_edges() {
+    let view = view((64, 48));
+
+    let plain = coloring(1, false, false).render(&strategy::Single, &view);
+    let full = coloring(3, false, false).render(&strategy::Single, &view);
+    let adaptive = coloring(3, false, true).render(&strategy::Single, &view);
+
+    let pixels = |image: &[u8]| image.chunks(3).map(<[u8]>::to_vec).collect::<Vec<_>>();
+    let (plain, full, adaptive) = (pixels(&plain), pixels(&full), pixels(&adaptive));
+
+    let refined = (0..adaptive.len())
+        .filter(|&i| adaptive[i] != plain[i])
+        .collect::<Vec<_>>();
+
+    assert!(!refined.is_empty());
+    assert!(refined.len() < adaptive.len() / 2);
+
+    // Every pixel is either left alone, or drawn as the full grid draws it.
+    for i in 0..adaptive.len() {
+        assert!(adaptive[i] == plain[i] || adaptive[i] == full[i]);
+    }
+}